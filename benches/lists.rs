@@ -0,0 +1,61 @@
+//! Times building and walking lists of growing length. Both take linear time, so the time per
+//! element should stay about the same as the lists get longer.
+//!
+//! Run with `cargo bench`.
+
+use std::time::{Duration, Instant};
+
+use f::{Interpreter, Value};
+
+const PROGRAM: &str = r"
+\build n acc -> if = n 0 acc build - n 1 push acc n
+\sum xs n acc -> if = n 0 acc sum tail xs - n 1 + acc head xs
+";
+
+const LENGTHS: [i64; 5] = [1_000, 2_000, 4_000, 8_000, 16_000];
+const RUNS: u32 = 10;
+
+/// The fastest of several runs of `f`, which is the least disturbed by anything else going on.
+fn time(mut f: impl FnMut()) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn report(name: &str, n: i64, elapsed: Duration) {
+    let per_element = elapsed.as_nanos() / n as u128;
+    println!("{name:<6} {n:>6} elements: {elapsed:>12?} ({per_element} ns per element)");
+}
+
+fn main() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_type_check(true);
+    interpreter
+        .load(PROGRAM)
+        .expect("benchmark program should load");
+
+    for n in LENGTHS {
+        let empty = Value::List(Default::default());
+        let build = |interpreter: &Interpreter| {
+            interpreter
+                .call("build", vec![Value::Int(n), empty.clone()])
+                .expect("build should succeed")
+        };
+
+        report("build", n, time(|| drop(build(&interpreter))));
+
+        let list = build(&interpreter);
+        let elapsed = time(|| {
+            let sum = interpreter
+                .call("sum", vec![list.clone(), Value::Int(n), Value::Int(0)])
+                .expect("sum should succeed");
+            assert_eq!(sum, Value::Int(n * (n + 1) / 2));
+        });
+        report("sum", n, elapsed);
+    }
+}
@@ -0,0 +1,982 @@
+use std::collections::HashMap;
+
+use lasso::Key;
+
+use crate::{
+    diagnostic::{Code, Diagnostic},
+    env::{Environment, FunctionBody, Symbol},
+    error::{Error, Result},
+    interpreter::{Value, STACK_GROWTH, STACK_RED_ZONE},
+    parser::{Arm, Expression, Pattern},
+    span::Span,
+    types::{Scheme, Type, TypeVar},
+};
+
+/// The types inferred for the functions of an environment, along with the errors found on the way.
+/// A function whose body has errors still gets the type inferred from the rest of it.
+pub struct Inference {
+    pub types: HashMap<Symbol, Scheme>,
+    pub errors: Vec<Error>,
+    /// Problems that don't stop the functions from running, like matches that miss some values.
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Infers a type for every function in `env`. Builtins and other functions with a signature keep
+/// it, host functions without one are taken to accept anything.
+pub fn infer(env: &Environment) -> Inference {
+    let mut checker = Checker::new(env);
+    checker.infer_functions();
+    Inference {
+        types: checker.schemes,
+        errors: checker.errors,
+        warnings: checker.warnings,
+    }
+}
+
+/// Checks the types of every function in `env`, failing with all the mismatches found.
+pub fn check(env: &Environment) -> Result<HashMap<Symbol, Scheme>> {
+    let Inference {
+        types, mut errors, ..
+    } = infer(env);
+    errors.sort_by_key(|error| error.span().map(|span| span.start));
+    match Error::many(errors) {
+        Some(error) => Err(error),
+        None => Ok(types),
+    }
+}
+
+/// Checks `expr` against the functions of `env`, which are expected to check already, and infers
+/// its type.
+pub fn check_expr(expr: &Expression, env: &Environment) -> Result<Scheme> {
+    let mut checker = Checker::new(env);
+    checker.infer_functions();
+    checker.errors.clear();
+
+    let ty = checker.infer(expr, &[]);
+    match Error::many(std::mem::take(&mut checker.errors)) {
+        Some(error) => Err(error),
+        None => Ok(checker.generalize(&ty)),
+    }
+}
+
+struct Checker<'a> {
+    env: &'a Environment,
+    /// What each type variable has been unified with, if anything yet.
+    bindings: Vec<Option<Type>>,
+    /// The types each constrained type variable is limited to.
+    constraints: HashMap<TypeVar, Vec<Type>>,
+    schemes: HashMap<Symbol, Scheme>,
+    /// Types of the functions being inferred together, which can only be used at one type until
+    /// they are generalized.
+    inferring: HashMap<Symbol, Type>,
+    errors: Vec<Error>,
+    warnings: Vec<Diagnostic>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(env: &'a Environment) -> Self {
+        Self {
+            env,
+            bindings: vec![],
+            constraints: HashMap::new(),
+            schemes: HashMap::new(),
+            inferring: HashMap::new(),
+            errors: vec![],
+            warnings: vec![],
+        }
+    }
+
+    fn fresh(&mut self) -> Type {
+        self.bindings.push(None);
+        Type::Var(self.bindings.len() - 1)
+    }
+
+    /// Infers the declared functions a group of mutually recursive functions at a time, each group
+    /// after the ones it calls so that those can be used at different types.
+    fn infer_functions(&mut self) {
+        let mut inferred = vec![];
+        for (symbol, func) in self.env.functions() {
+            match (func.signature(), func.body()) {
+                (Some(signature), _) => {
+                    self.schemes.insert(symbol, signature.clone());
+                }
+                (None, FunctionBody::Normal(body)) => {
+                    inferred.push((symbol, func.args(), body, func.annotation()))
+                }
+                (None, _) => {
+                    let params = (0..func.args()).map(|_| self.fresh()).collect::<Vec<_>>();
+                    let ret = self.fresh();
+                    let ty = Type::function(params, ret);
+                    self.schemes.insert(symbol, Scheme::new(ty, HashMap::new()));
+                }
+            }
+        }
+        inferred.sort_by_key(|(symbol, ..)| symbol.into_usize());
+
+        let graph = inferred
+            .iter()
+            .map(|(_, _, body, _)| {
+                let mut calls = vec![];
+                calls_in(body, &mut calls);
+                calls
+                    .into_iter()
+                    .filter_map(|symbol| inferred.iter().position(|(s, ..)| *s == symbol))
+                    .collect()
+            })
+            .collect::<Vec<Vec<_>>>();
+
+        for group in components(&graph) {
+            let mut locals = vec![];
+            let mut rigid = vec![];
+            for &i in &group {
+                let (symbol, args, _, annotation) = inferred[i];
+                let params = (0..args).map(|_| self.fresh()).collect::<Vec<_>>();
+                let ret = self.fresh();
+                self.inferring
+                    .insert(symbol, Type::function(params.clone(), ret.clone()));
+
+                // Variables in an annotation stand for any type, so they get fresh variables
+                // that must not be unified with anything else by the body
+                if let Some(annotation) = annotation {
+                    let vars = annotation
+                        .vars()
+                        .into_iter()
+                        .map(|var| (var, self.fresh()))
+                        .collect::<HashMap<_, _>>();
+                    let annotated = annotation.params.iter().zip(&params);
+                    for (ty, local) in annotated.chain([(&annotation.ret, &ret)]) {
+                        if let Some(ty) = ty {
+                            let _ = self.unify(local, &substitute(ty, &vars));
+                        }
+                    }
+                    rigid.push((symbol, annotation, vars.into_values().collect::<Vec<_>>()));
+                }
+                locals.push((params, ret));
+            }
+
+            for (&i, (params, ret)) in group.iter().zip(locals) {
+                let body = inferred[i].2;
+                let params = params.into_iter().map(Scheme::mono).collect::<Vec<_>>();
+                let ty = self.infer(body, &params);
+                self.expect(&ret, &ty, body.span());
+            }
+
+            for (symbol, annotation, vars) in rigid {
+                let resolved = vars.iter().map(|var| self.resolve(var)).collect::<Vec<_>>();
+                let general = resolved
+                    .iter()
+                    .enumerate()
+                    .all(|(i, ty)| matches!(ty, Type::Var(_)) && !resolved[..i].contains(ty));
+                if !general {
+                    let ty = self.generalize(&self.inferring[&symbol]);
+                    self.errors.push(
+                        Diagnostic::error(format!(
+                            "{} is less general than its annotation",
+                            self.env.get_name(symbol)
+                        ))
+                        .with_code(Code::TypeMismatch)
+                        .with_label(
+                            annotation.span.clone(),
+                            format!("annotated as {annotation}"),
+                        )
+                        .with_note(format!("its body has type {ty}"))
+                        .with_help("type variables stand for any type, use a concrete type instead")
+                        .into(),
+                    );
+                }
+            }
+
+            for &i in &group {
+                let symbol = inferred[i].0;
+                let ty = self.inferring.remove(&symbol).unwrap();
+                let scheme = self.generalize(&ty);
+                self.schemes.insert(symbol, scheme);
+            }
+        }
+    }
+
+    fn infer(&mut self, expr: &Expression, locals: &[Scheme]) -> Type {
+        stacker::maybe_grow(STACK_RED_ZONE, STACK_GROWTH, || self.infer_(expr, locals))
+    }
+
+    fn infer_(&mut self, expr: &Expression, locals: &[Scheme]) -> Type {
+        match expr {
+            Expression::App(symbol, params, span) => {
+                let mut ty = self.function(*symbol);
+                let name = self.env.get_name(*symbol);
+                let name_span = name_span(name, params.first(), span);
+                for param in params.iter() {
+                    let (expected, ret) = self.split(&ty);
+                    let found = self.infer(param, locals);
+                    self.expect_argument(&expected, &found, param.span(), &name_span, name);
+                    ty = ret;
+                }
+                ty
+            }
+            Expression::Partial(symbol, params, span) => {
+                let mut ty = self.function(*symbol);
+                let name = self.env.get_name(*symbol);
+                let name_span = Some(span.start..span.start + name.len());
+                let mut holes = vec![];
+                for param in params.iter() {
+                    let (expected, ret) = self.split(&ty);
+                    match param {
+                        Some(param) => {
+                            let found = self.infer(param, locals);
+                            self.expect_argument(&expected, &found, param.span(), &name_span, name);
+                        }
+                        None => holes.push(expected),
+                    }
+                    ty = ret;
+                }
+                Type::function(holes, ty)
+            }
+            Expression::Call(callee, params, _) => {
+                let mut ty = self.infer(callee, locals);
+                for param in params.iter() {
+                    let resolved = self.resolve(&ty);
+                    if !matches!(resolved, Type::Fun(..) | Type::Var(_)) {
+                        self.errors.push(
+                            Diagnostic::error(format!("cannot apply {resolved} as a function"))
+                                .with_code(Code::NotAFunction)
+                                .with_label(callee.span(), format!("this is {resolved}"))
+                                .into(),
+                        );
+                        return self.fresh();
+                    }
+
+                    let (expected, ret) = self.split(&ty);
+                    let found = self.infer(param, locals);
+                    self.expect(&expected, &found, param.span());
+                    ty = ret;
+                }
+                ty
+            }
+            Expression::Lambda(lambda, _) => {
+                let params = (0..lambda.args).map(|_| self.fresh()).collect::<Vec<_>>();
+                let mut scope = locals.to_vec();
+                scope.extend(params.iter().cloned().map(Scheme::mono));
+                let ret = self.infer(&lambda.body, &scope);
+                Type::function(params, ret)
+            }
+            Expression::Ref(symbol, _) => self.function(*symbol),
+            Expression::Arg(idx, _) => self.instantiate(&locals[*idx]),
+            Expression::Literal(value, _) => self.literal(value),
+            Expression::Do(exprs, _) => {
+                let mut ty = Type::Nothing;
+                for expr in exprs.iter() {
+                    ty = self.infer(expr, locals);
+                }
+                ty
+            }
+            Expression::Let(value, body, _) => {
+                let ty = self.infer(value, locals);
+                let scheme = self.generalize_local(&ty, locals);
+                let mut scope = locals.to_vec();
+                scope.push(scheme);
+                self.infer(body, &scope)
+            }
+            Expression::List(items, _) => {
+                let element = self.fresh();
+                for item in items.iter() {
+                    let found = self.infer(item, locals);
+                    if self.unify(&element, &found).is_err() {
+                        let error = self
+                            .mismatch(&element, &found, item.span())
+                            .with_note("the elements of a list all have the same type");
+                        self.errors.push(error.into());
+                    }
+                }
+                Type::list(element)
+            }
+            Expression::Cond(branches, _) => self.cond(branches, locals),
+            Expression::Match(value, arms, span) => self.match_(value, arms, span, locals),
+            Expression::Temp => self.fresh(),
+        }
+    }
+
+    fn match_(&mut self, value: &Expression, arms: &[Arm], span: &Span, locals: &[Scheme]) -> Type {
+        let errors = self.errors.len();
+        let scrutinee = self.infer(value, locals);
+        let ty = self.fresh();
+        for arm in arms {
+            let mut scope = locals.to_vec();
+            let found = self.pattern(&arm.pattern, &mut scope);
+            self.expect(&scrutinee, &found, arm.pattern.span());
+            if let Some(guard) = &arm.guard {
+                let found = self.infer(guard, &scope);
+                self.expect(&Type::Bool, &found, guard.span());
+            }
+            let found = self.infer(&arm.body, &scope);
+            self.expect(&ty, &found, arm.body.span());
+        }
+
+        // Whether the arms cover every value only means something once they agree on its type.
+        // Guarded arms might not match, so they don't count
+        let rows = arms
+            .iter()
+            .filter(|arm| arm.guard.is_none())
+            .map(|arm| vec![arm.pattern.clone()])
+            .collect::<Vec<_>>();
+        if self.errors.len() == errors && !self.exhaustive(&rows, std::slice::from_ref(&scrutinee))
+        {
+            let scrutinee = self.resolve(&scrutinee);
+            self.warnings.push(
+                Diagnostic::warning("match doesn't cover every value")
+                    .with_label(
+                        span.start..value.span().end,
+                        format!("some values of {scrutinee} match no arm"),
+                    )
+                    .with_help("add a `_` arm at the end to match the rest"),
+            );
+        }
+        ty
+    }
+
+    /// The type of values `pattern` can match, adding the types of the names it binds to `scope`.
+    fn pattern(&mut self, pattern: &Pattern, scope: &mut Vec<Scheme>) -> Type {
+        match pattern {
+            Pattern::Wildcard(_) => self.fresh(),
+            Pattern::Bind(_) => {
+                let ty = self.fresh();
+                scope.push(Scheme::mono(ty.clone()));
+                ty
+            }
+            Pattern::Literal(value, _) => self.literal(value),
+            Pattern::List(items, rest, _) => {
+                let element = self.fresh();
+                for item in items {
+                    let found = self.pattern(item, scope);
+                    if self.unify(&element, &found).is_err() {
+                        let error = self
+                            .mismatch(&element, &found, item.span())
+                            .with_note("the elements of a list all have the same type");
+                        self.errors.push(error.into());
+                    }
+                }
+                let ty = Type::list(element);
+                if let Some(rest) = rest {
+                    let found = self.pattern(rest, scope);
+                    self.expect(&ty, &found, rest.span());
+                }
+                ty
+            }
+        }
+    }
+
+    /// Whether every combination of values of `types` is matched by one of `rows`, each of which
+    /// has a pattern per type. Bools, `none` and lists are split into the ways they can be built,
+    /// any other type is only covered by a pattern matching everything.
+    fn exhaustive(&self, rows: &[Vec<Pattern>], types: &[Type]) -> bool {
+        let Some((first, rest)) = types.split_first() else {
+            return !rows.is_empty();
+        };
+
+        let first = self.resolve(first);
+        let constructors = match &first {
+            Type::Bool => vec![Shape::True, Shape::False],
+            Type::Nothing => vec![Shape::None],
+            Type::List(_) => vec![Shape::Empty, Shape::Cons],
+            _ => vec![],
+        };
+        let complete = !constructors.is_empty()
+            && constructors
+                .iter()
+                .all(|shape| rows.iter().any(|row| shape.starts(&row[0])));
+
+        if complete {
+            constructors.into_iter().all(|shape| {
+                let mut types = match (&shape, &first) {
+                    (Shape::Cons, Type::List(element)) => vec![*element.clone(), first.clone()],
+                    _ => vec![],
+                };
+                types.extend_from_slice(rest);
+                let rows = rows
+                    .iter()
+                    .filter_map(|row| shape.specialize(row))
+                    .collect::<Vec<_>>();
+                self.exhaustive(&rows, &types)
+            })
+        } else {
+            let rows = rows
+                .iter()
+                .filter(|row| row[0].is_irrefutable())
+                .map(|row| row[1..].to_vec())
+                .collect::<Vec<_>>();
+            self.exhaustive(&rows, rest)
+        }
+    }
+
+    /// Conditions are bools and results all have the same type, which is the type of the cond.
+    fn cond(&mut self, branches: &[Expression], locals: &[Scheme]) -> Type {
+        let ty = self.fresh();
+        for branch in branches.chunks(2) {
+            if let [test, _] = branch {
+                let found = self.infer(test, locals);
+                self.expect(&Type::Bool, &found, test.span());
+            }
+            let result = branch.last().unwrap();
+            let found = self.infer(result, locals);
+            self.expect(&ty, &found, result.span());
+        }
+        ty
+    }
+
+    fn literal(&mut self, value: &Value) -> Type {
+        match value {
+            Value::Int(_) | Value::Float(_) => Type::Num,
+            Value::String(_) => Type::String,
+            Value::Bool(_) => Type::Bool,
+            Value::Nothing => Type::Nothing,
+            Value::List(items) => {
+                let element = self.fresh();
+                for item in items {
+                    let ty = self.literal(item);
+                    // A list built at runtime may mix types, which is only a problem once
+                    // something expects otherwise
+                    if self.unify(&element, &ty).is_err() {
+                        return Type::list(self.fresh());
+                    }
+                }
+                Type::list(element)
+            }
+            Value::Function(_) => self.fresh(),
+        }
+    }
+
+    /// The type of a use of a declared function.
+    fn function(&mut self, symbol: Symbol) -> Type {
+        if let Some(ty) = self.inferring.get(&symbol) {
+            return ty.clone();
+        }
+        match self.schemes.get(&symbol).cloned() {
+            Some(scheme) => self.instantiate(&scheme),
+            None => self.fresh(),
+        }
+    }
+
+    /// Splits a function type into its first parameter and what it returns after taking it.
+    fn split(&mut self, ty: &Type) -> (Type, Type) {
+        match self.resolve(ty) {
+            Type::Fun(param, ret) => (*param, *ret),
+            ty => {
+                let (param, ret) = (self.fresh(), self.fresh());
+                // Only a type variable can still turn out to be a function, anything else has
+                // already been reported by the caller
+                let _ = self.unify(
+                    &ty,
+                    &Type::Fun(Box::new(param.clone()), Box::new(ret.clone())),
+                );
+                (param, ret)
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: &Type, found: &Type, span: Span) {
+        if self.unify(expected, found).is_err() {
+            let error = self.mismatch(expected, found, span);
+            self.errors.push(error.into());
+        }
+    }
+
+    fn expect_argument(
+        &mut self,
+        expected: &Type,
+        found: &Type,
+        span: Span,
+        name_span: &Option<Span>,
+        name: &str,
+    ) {
+        if self.unify(expected, found).is_err() {
+            let mut error = self.mismatch(expected, found, span);
+            if let Some(name_span) = name_span {
+                error = error.with_secondary(name_span.clone(), format!("argument of {name}"));
+            }
+            self.errors.push(error.into());
+        }
+    }
+
+    fn mismatch(&self, expected: &Type, found: &Type, span: Span) -> Diagnostic {
+        let (expected, found) = (self.describe(expected), self.describe(found));
+        Diagnostic::error("mismatched types")
+            .with_code(Code::TypeMismatch)
+            .with_label(span, format!("expected {expected}, found {found}"))
+    }
+
+    /// How a type is shown in an error, spelling out the types a constrained variable can be.
+    fn describe(&self, ty: &Type) -> String {
+        match self.resolve(ty) {
+            Type::Var(var) if self.constraints.contains_key(&var) => self.constraints[&var]
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(" or "),
+            ty => ty.to_string(),
+        }
+    }
+
+    fn unify(&mut self, a: &Type, b: &Type) -> core::result::Result<(), ()> {
+        match (self.shallow(a), self.shallow(b)) {
+            (Type::Var(a), Type::Var(b)) if a == b => Ok(()),
+            (Type::Var(var), ty) | (ty, Type::Var(var)) => self.bind(var, ty),
+            (Type::Fun(a_param, a_ret), Type::Fun(b_param, b_ret)) => {
+                self.unify(&a_param, &b_param)?;
+                self.unify(&a_ret, &b_ret)
+            }
+            (Type::List(a), Type::List(b)) => self.unify(&a, &b),
+            (a, b) if a == b => Ok(()),
+            _ => Err(()),
+        }
+    }
+
+    fn bind(&mut self, var: TypeVar, ty: Type) -> core::result::Result<(), ()> {
+        if self.resolve(&ty).vars().contains(&var) {
+            return Err(());
+        }
+
+        if let Some(allowed) = self.constraints.get(&var).cloned() {
+            match &ty {
+                Type::Var(other) => {
+                    let allowed = match self.constraints.get(other) {
+                        Some(theirs) => allowed
+                            .into_iter()
+                            .filter(|ty| theirs.contains(ty))
+                            .collect(),
+                        None => allowed,
+                    };
+                    if allowed.is_empty() {
+                        return Err(());
+                    }
+                    self.constraints.insert(*other, allowed);
+                }
+                ty if !allowed.contains(ty) => return Err(()),
+                _ => {}
+            }
+        }
+
+        self.bindings[var] = Some(ty);
+        Ok(())
+    }
+
+    /// Follows the bindings of a type variable until reaching something else or an unbound one.
+    fn shallow(&self, ty: &Type) -> Type {
+        let mut ty = ty.clone();
+        while let Type::Var(var) = ty {
+            match &self.bindings[var] {
+                Some(bound) => ty = bound.clone(),
+                None => break,
+            }
+        }
+        ty
+    }
+
+    /// Substitutes everything known about the type variables in `ty`.
+    fn resolve(&self, ty: &Type) -> Type {
+        match self.shallow(ty) {
+            Type::Fun(param, ret) => {
+                Type::Fun(Box::new(self.resolve(&param)), Box::new(self.resolve(&ret)))
+            }
+            Type::List(element) => Type::list(self.resolve(&element)),
+            ty => ty,
+        }
+    }
+
+    fn generalize(&self, ty: &Type) -> Scheme {
+        Scheme::new(self.resolve(ty), self.constraints.clone())
+    }
+
+    /// Generalizes the type of a `let` over the variables nothing else in scope refers to, so that
+    /// a local function can be used at different types like a declared one.
+    fn generalize_local(&self, ty: &Type, locals: &[Scheme]) -> Scheme {
+        let ty = self.resolve(ty);
+        let mut fixed = vec![];
+        for local in locals {
+            let vars = self.resolve(&local.ty).vars();
+            fixed.extend(vars.into_iter().filter(|var| !local.vars.contains(var)));
+        }
+        // The functions being inferred can't be generalized yet, and neither can what refers to
+        // their types
+        for ty in self.inferring.values() {
+            fixed.extend(self.resolve(ty).vars());
+        }
+
+        let vars = ty
+            .vars()
+            .into_iter()
+            .filter(|var| !fixed.contains(var))
+            .collect::<Vec<_>>();
+        let constraints = vars
+            .iter()
+            .filter_map(|var| Some((*var, self.constraints.get(var)?.clone())))
+            .collect();
+        Scheme {
+            vars,
+            constraints,
+            ty,
+        }
+    }
+
+    fn instantiate(&mut self, scheme: &Scheme) -> Type {
+        let fresh = scheme
+            .vars
+            .iter()
+            .map(|&var| (var, self.fresh()))
+            .collect::<HashMap<_, _>>();
+        for (var, allowed) in &scheme.constraints {
+            if let Type::Var(new) = fresh[var] {
+                self.constraints.insert(new, allowed.clone());
+            }
+        }
+        substitute(&scheme.ty, &fresh)
+    }
+}
+
+fn substitute(ty: &Type, vars: &HashMap<TypeVar, Type>) -> Type {
+    match ty {
+        Type::Var(var) => vars.get(var).cloned().unwrap_or(Type::Var(*var)),
+        Type::Fun(param, ret) => Type::Fun(
+            Box::new(substitute(param, vars)),
+            Box::new(substitute(ret, vars)),
+        ),
+        Type::List(element) => Type::list(substitute(element, vars)),
+        ty => ty.clone(),
+    }
+}
+
+/// Where the name of a called function is, which is at the start of the call unless it was written
+/// between its arguments in braces.
+fn name_span(name: &str, first: Option<&Expression>, span: &Span) -> Option<Span> {
+    match first {
+        Some(first) if first.span().start == span.start => None,
+        _ => Some(span.start..span.start + name.len()),
+    }
+}
+
+/// The declared functions `expr` uses.
+pub(crate) fn calls_in(expr: &Expression, calls: &mut Vec<Symbol>) {
+    stacker::maybe_grow(STACK_RED_ZONE, STACK_GROWTH, || calls_in_(expr, calls))
+}
+
+fn calls_in_(expr: &Expression, calls: &mut Vec<Symbol>) {
+    match expr {
+        Expression::App(symbol, params, _) => {
+            calls.push(*symbol);
+            params.iter().for_each(|param| calls_in(param, calls));
+        }
+        Expression::Partial(symbol, params, _) => {
+            calls.push(*symbol);
+            params
+                .iter()
+                .flatten()
+                .for_each(|param| calls_in(param, calls));
+        }
+        Expression::Call(callee, params, _) => {
+            calls_in(callee, calls);
+            params.iter().for_each(|param| calls_in(param, calls));
+        }
+        Expression::Lambda(lambda, _) => calls_in(&lambda.body, calls),
+        Expression::List(items, _) => items.iter().for_each(|item| calls_in(item, calls)),
+        Expression::Do(exprs, _) | Expression::Cond(exprs, _) => {
+            exprs.iter().for_each(|expr| calls_in(expr, calls))
+        }
+        Expression::Let(value, body, _) => {
+            calls_in(value, calls);
+            calls_in(body, calls);
+        }
+        Expression::Match(value, arms, _) => {
+            calls_in(value, calls);
+            for arm in arms.iter() {
+                arm.guard.iter().for_each(|guard| calls_in(guard, calls));
+                calls_in(&arm.body, calls);
+            }
+        }
+        Expression::Ref(symbol, _) => calls.push(*symbol),
+        Expression::Arg(..) | Expression::Literal(..) | Expression::Temp => {}
+    }
+}
+
+/// The ways a value of a type with patterns for each of them can be built, for checking whether
+/// the arms of a match cover every value.
+#[derive(PartialEq)]
+enum Shape {
+    True,
+    False,
+    None,
+    Empty,
+    /// A list with a first element, followed by the list of the rest.
+    Cons,
+}
+
+impl Shape {
+    /// Whether `pattern` only matches values built this way.
+    fn starts(&self, pattern: &Pattern) -> bool {
+        match (self, pattern) {
+            (Self::True, Pattern::Literal(Value::Bool(true), _))
+            | (Self::False, Pattern::Literal(Value::Bool(false), _))
+            | (Self::None, Pattern::Literal(Value::Nothing, _)) => true,
+            (Self::Empty, Pattern::List(items, None, _)) => items.is_empty(),
+            (Self::Cons, Pattern::List(items, _, _)) => !items.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// The patterns `row` has for the parts of values built this way followed by the rest of it,
+    /// or `None` if its first pattern can't match them.
+    fn specialize(&self, row: &[Pattern]) -> Option<Vec<Pattern>> {
+        let (first, rest) = row.split_first().unwrap();
+        let mut parts = match first {
+            _ if first.is_irrefutable() => match self {
+                Self::Cons => vec![first.clone(), first.clone()],
+                _ => vec![],
+            },
+            // Past the last element, `[x : rest]` leaves the rest of the list to `rest`
+            Pattern::List(items, Some(tail), _) if items.len() == 1 && *self == Self::Cons => {
+                vec![items[0].clone(), *tail.clone()]
+            }
+            Pattern::List(items, tail, span) if *self == Self::Cons && !items.is_empty() => vec![
+                items[0].clone(),
+                Pattern::List(items[1..].to_vec(), tail.clone(), span.clone()),
+            ],
+            _ if self.starts(first) => vec![],
+            _ => return None,
+        };
+        parts.extend_from_slice(rest);
+        Some(parts)
+    }
+}
+
+/// The strongly connected components of `graph`, each one after the components it has edges to.
+fn components(graph: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    struct Tarjan<'a> {
+        graph: &'a [Vec<usize>],
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        next: usize,
+        components: Vec<Vec<usize>>,
+    }
+
+    impl Tarjan<'_> {
+        fn visit(&mut self, node: usize) {
+            self.index[node] = Some(self.next);
+            self.low[node] = self.next;
+            self.next += 1;
+            self.stack.push(node);
+            self.on_stack[node] = true;
+
+            for &edge in &self.graph[node] {
+                match self.index[edge] {
+                    None => {
+                        self.visit(edge);
+                        self.low[node] = self.low[node].min(self.low[edge]);
+                    }
+                    Some(index) if self.on_stack[edge] => {
+                        self.low[node] = self.low[node].min(index);
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            if Some(self.low[node]) == self.index[node] {
+                let mut component = vec![];
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                component.reverse();
+                self.components.push(component);
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        graph,
+        index: vec![None; graph.len()],
+        low: vec![0; graph.len()],
+        stack: vec![],
+        on_stack: vec![false; graph.len()],
+        next: 0,
+        components: vec![],
+    };
+    for node in 0..graph.len() {
+        if tarjan.index[node].is_none() {
+            tarjan.visit(node);
+        }
+    }
+    tarjan.components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{diagnostic::Severity, env::default_env, parser::parse_file, tokenizer::tokenize};
+
+    fn load(src: &str) -> Environment {
+        let mut env = default_env();
+        parse_file(&tokenize(src).unwrap(), &mut env).unwrap();
+        env
+    }
+
+    /// The type inferred for `name` in `src`, which must check.
+    fn type_of(src: &str, name: &str) -> String {
+        let env = load(src);
+        let types = check(&env).unwrap_or_else(|e| panic!("{src} should check: {e:?}"));
+        types[&env.get_symbol(name).unwrap()].to_string()
+    }
+
+    fn errors(src: &str) -> Vec<Error> {
+        infer(&load(src)).errors
+    }
+
+    fn warnings(src: &str) -> Vec<Diagnostic> {
+        infer(&load(src)).warnings
+    }
+
+    #[test]
+    fn builtin_signatures() {
+        let env = default_env();
+        let types = check(&env).unwrap();
+        let builtin = |name| types[&env.get_symbol(name).unwrap()].to_string();
+        assert_eq!(builtin("head"), "List a -> a");
+        assert_eq!(builtin("if"), "Bool -> a -> a -> a");
+        assert_eq!(builtin("+"), "a -> a -> a where a: Num | String");
+    }
+
+    #[test]
+    fn constrained_addition() {
+        assert_eq!(
+            type_of(r"\add a b -> + a b", "add"),
+            "a -> a -> a where a: Num | String"
+        );
+        assert_eq!(type_of(r"\inc n -> + n 1", "inc"), "Num -> Num");
+        assert_eq!(
+            type_of(r#"\greet s -> + "hi " s"#, "greet"),
+            "String -> String"
+        );
+
+        let found = errors(r#"\bad -> + 1 "a""#);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].code(), Some(Code::TypeMismatch));
+        let found = errors(r"\bad -> + true false");
+        assert!(!found.is_empty());
+        assert!(found
+            .iter()
+            .all(|error| error.code() == Some(Code::TypeMismatch)));
+    }
+
+    #[test]
+    fn cond_branches() {
+        assert_eq!(
+            type_of(
+                r#"\sign n -> cond [< n 0 "negative" = n 0 "zero" "positive"]"#,
+                "sign"
+            ),
+            "Num -> String"
+        );
+        assert_eq!(
+            type_of(r"\pick b x -> cond [b x]", "pick"),
+            "Bool -> a -> a"
+        );
+
+        for src in [r#"\bad -> cond [1 "a"]"#, r#"\bad -> cond [true 1 "a"]"#] {
+            let found = errors(src);
+            assert_eq!(found.len(), 1, "{src}");
+            assert_eq!(found[0].code(), Some(Code::TypeMismatch), "{src}");
+        }
+    }
+
+    #[test]
+    fn mutual_recursion() {
+        let src = r"
+\even n -> if = n 0 true odd - n 1
+\odd n -> if = n 0 false even - n 1
+";
+        assert_eq!(type_of(src, "even"), "Num -> Bool");
+        assert_eq!(type_of(src, "odd"), "Num -> Bool");
+
+        // Functions are generalized before the functions calling them are inferred
+        let src = r#"
+\id x -> x
+\both -> pair len [id 1] len [id "a"]
+"#;
+        assert_eq!(type_of(src, "id"), "a -> a");
+        assert_eq!(type_of(src, "both"), "List Num");
+
+        // but not within their own group
+        let src = r#"
+\ping x -> pong x
+\pong y -> do [ping 1 ping "a"]
+"#;
+        assert_eq!(errors(src).len(), 1);
+    }
+
+    #[test]
+    fn let_generalization() {
+        let src = r#"\poly -> let f \x -> x do [apply f 1 apply f "a"]"#;
+        assert_eq!(type_of(src, "poly"), "String");
+        assert_eq!(errors(r#"\bad -> let f \x -> + x 1 apply f "a""#).len(), 1);
+        // A let referring to a parameter can't be generalized over its type
+        assert_eq!(type_of(r"\keep n -> let m n {m + 1}", "keep"), "Num -> Num");
+    }
+
+    #[test]
+    fn annotations() {
+        assert_eq!(type_of(r"\first x: a y -> x", "first"), "a -> b -> a");
+        assert_eq!(type_of(r"\num x: Num -> x", "num"), "Num -> Num");
+
+        let found = errors(r"\bad x: a -> a -> + x 1");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].code(), Some(Code::TypeMismatch));
+        assert_eq!(
+            found[0].message(),
+            "bad is less general than its annotation"
+        );
+
+        assert_eq!(errors(r"\wrong x: Num -> String -> x").len(), 1);
+    }
+
+    #[test]
+    fn exhaustive_bools() {
+        assert!(warnings(r"\f b -> match b [true -> 1 false -> 0]").is_empty());
+        assert!(warnings(r"\f b -> match b [true -> 1 _ -> 0]").is_empty());
+        let found = warnings(r"\f b -> match b [true -> 1]");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].severity, Severity::Warning);
+        assert!(found[0].labels[0].message.contains("Bool"));
+    }
+
+    #[test]
+    fn exhaustive_lists() {
+        assert!(warnings(r"\f xs -> match xs [[] -> 0 [x : _] -> x]").is_empty());
+        assert!(warnings(r"\f xs -> match xs [[] -> 0 [_] -> 1 [_ _ : _] -> 2]").is_empty());
+        assert!(
+            warnings(r"\f xs -> match xs [[] -> 0 [true : _] -> 1 [false : _] -> 2]").is_empty()
+        );
+        assert_eq!(warnings(r"\f xs -> match xs [[x : _] -> x]").len(), 1);
+        assert_eq!(warnings(r"\f xs -> match xs [[] -> 0 [_] -> 1]").len(), 1);
+        assert_eq!(
+            warnings(r"\f xs -> match xs [[] -> 0 [true : _] -> 1]").len(),
+            1
+        );
+    }
+
+    #[test]
+    fn exhaustive_guards_and_literals() {
+        // Guarded arms might not match, so they don't cover anything
+        assert_eq!(warnings(r"\f n -> match n [k if > k 0 -> 1]").len(), 1);
+        assert_eq!(
+            warnings(r"\f b -> match b [true -> 1 false if true -> 0]").len(),
+            1
+        );
+        assert!(warnings(r"\f n -> match n [k if > k 0 -> 1 _ -> 0]").is_empty());
+        // Numbers and strings are only covered by an arm matching anything
+        assert_eq!(warnings(r"\f n -> match n [0 -> 1 1 -> 0]").len(), 1);
+        assert!(warnings(r"\f n -> match n [0 -> 1 n -> n]").is_empty());
+        // Matches with type errors aren't checked for coverage
+        assert!(warnings(r#"\f n -> match n [0 -> 1 "a" -> 0]"#).is_empty());
+    }
+}
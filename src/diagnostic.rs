@@ -0,0 +1,400 @@
+use std::fmt::Display;
+
+use ansi_term::{
+    Color::{Blue, Red, Yellow},
+    Style,
+};
+
+use crate::span::{SourceMap, Span};
+
+/// Spans covering more lines than this only show their first and last lines.
+const MAX_SPAN_LINES: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    fn style(self) -> Style {
+        match self {
+            Self::Error => Red.bold(),
+            Self::Warning => Yellow.bold(),
+            Self::Note => Blue.bold(),
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Error => "error",
+                Self::Warning => "warning",
+                Self::Note => "note",
+            }
+        )
+    }
+}
+
+/// Stable codes for the kinds of errors, which stay the same when their messages change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Code {
+    InvalidToken,
+    UnexpectedToken,
+    UnknownFunction,
+    UnexpectedEof,
+    MisplacedHole,
+    WrongType,
+    Arithmetic,
+    EmptyList,
+    NotAFunction,
+    TooManyArguments,
+    CallDepth,
+    NoMain,
+    TypeMismatch,
+    InvalidAnnotation,
+    IndexOutOfBounds,
+    NoMatch,
+    InvalidOperator,
+    ArityChanged,
+}
+
+impl Code {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::InvalidToken => "E0001",
+            Self::UnexpectedToken => "E0002",
+            Self::UnknownFunction => "E0003",
+            Self::UnexpectedEof => "E0004",
+            Self::MisplacedHole => "E0005",
+            Self::WrongType => "E0006",
+            Self::Arithmetic => "E0007",
+            Self::EmptyList => "E0008",
+            Self::NotAFunction => "E0009",
+            Self::TooManyArguments => "E0010",
+            Self::CallDepth => "E0011",
+            Self::NoMain => "E0012",
+            Self::TypeMismatch => "E0013",
+            Self::InvalidAnnotation => "E0014",
+            Self::IndexOutOfBounds => "E0015",
+            Self::NoMatch => "E0017",
+            Self::InvalidOperator => "E0018",
+            Self::ArityChanged => "E0019",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::InvalidToken => "invalid token",
+            Self::UnexpectedToken => "unexpected token",
+            Self::UnknownFunction => "unknown function",
+            Self::UnexpectedEof => "unexpected end of input",
+            Self::MisplacedHole => "misplaced `_`",
+            Self::WrongType => "wrong argument type",
+            Self::Arithmetic => "arithmetic error",
+            Self::EmptyList => "empty list",
+            Self::NotAFunction => "not a function",
+            Self::TooManyArguments => "too many arguments",
+            Self::CallDepth => "maximum call depth exceeded",
+            Self::NoMain => "no main function",
+            Self::TypeMismatch => "type mismatch",
+            Self::InvalidAnnotation => "invalid type annotation",
+            Self::IndexOutOfBounds => "index out of bounds",
+            Self::NoMatch => "no branch matched",
+            Self::InvalidOperator => "invalid operator",
+            Self::ArityChanged => "changed number of arguments",
+        }
+    }
+}
+
+impl Display for Code {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    /// The primary label marks where the problem is, secondary ones add context.
+    pub primary: bool,
+}
+
+/// A message about a piece of source code, with labelled spans pointing into it and notes and
+/// help text printed below them.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<Code>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            code: None,
+            message: message.into(),
+            labels: vec![],
+            notes: vec![],
+            help: vec![],
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn with_code(mut self, code: Code) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    pub fn primary_span(&self) -> Option<&Span> {
+        self.labels.iter().find(|l| l.primary).map(|l| &l.span)
+    }
+
+    /// Prints the diagnostic with the lines of the source its primary label points into, leaving
+    /// out labels that point into other sources.
+    pub fn render_in(&self, sources: &SourceMap) {
+        let mut relative = self.clone();
+        relative.labels.clear();
+        let mut file = "";
+
+        let span = self.primary_span().or(self.labels.first().map(|l| &l.span));
+        if let Some((span, (text, relative_span))) =
+            span.and_then(|span| Some((span, sources.find(span)?)))
+        {
+            let base = span.start - relative_span.start;
+            file = text;
+            relative.labels = self
+                .labels
+                .iter()
+                .filter_map(|label| {
+                    let (_, span) = sources.find(&label.span)?;
+                    (label.span.start - span.start == base).then(|| Label {
+                        span,
+                        ..label.clone()
+                    })
+                })
+                .collect();
+        }
+
+        relative.render(file);
+    }
+
+    /// Prints the diagnostic with the lines of `file` its labels point at.
+    pub fn render(&self, file: &str) {
+        let style = self.severity.style();
+        match self.code {
+            Some(code) => println!(
+                "{}: {}",
+                style.paint(format!("{}[{}]", self.severity, code)),
+                Style::new().bold().paint(&self.message)
+            ),
+            None => println!(
+                "{}: {}",
+                style.paint(self.severity.to_string()),
+                Style::new().bold().paint(&self.message)
+            ),
+        }
+
+        let source = Source::new(file);
+        let mut labels = self
+            .labels
+            .iter()
+            .filter(|label| source.contains(&label.span))
+            .collect::<Vec<_>>();
+        labels.sort_by_key(|label| (label.span.start, !label.primary));
+
+        let last_line = labels
+            .iter()
+            .map(|label| source.line(label.span.end))
+            .max()
+            .unwrap_or(0);
+        let gutter = " ".repeat((last_line + 1).to_string().len());
+
+        if let Some(primary) = labels.iter().find(|l| l.primary).or(labels.first()) {
+            let line = source.line(primary.span.start);
+            let column = file[source.starts[line]..primary.span.start]
+                .chars()
+                .count();
+            println!(
+                "{}{} {}:{}",
+                gutter,
+                Blue.bold().paint("-->"),
+                line + 1,
+                column + 1
+            );
+            println!("{} {}", gutter, Blue.bold().paint("|"));
+            source.snippet(&labels, self.severity, &gutter);
+        }
+
+        for note in &self.notes {
+            println!("{} {} note: {}", gutter, Blue.bold().paint("="), note);
+        }
+        for help in &self.help {
+            println!("{} {} help: {}", gutter, Blue.bold().paint("="), help);
+        }
+    }
+}
+
+/// A source file split into lines, for turning spans into what to print.
+struct Source<'a> {
+    text: &'a str,
+    /// Byte offset each line starts at.
+    starts: Vec<usize>,
+}
+
+impl<'a> Source<'a> {
+    fn new(text: &'a str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { text, starts }
+    }
+
+    fn contains(&self, span: &Span) -> bool {
+        span.start <= span.end
+            && span.end <= self.text.len()
+            && self.text.is_char_boundary(span.start)
+            && self.text.is_char_boundary(span.end)
+    }
+
+    /// The line `offset` is on, counting from zero.
+    fn line(&self, offset: usize) -> usize {
+        self.starts.partition_point(|&start| start <= offset) - 1
+    }
+
+    fn line_text(&self, line: usize) -> &'a str {
+        let end = self
+            .starts
+            .get(line + 1)
+            .map_or(self.text.len(), |&e| e - 1);
+        &self.text[self.starts[line]..end]
+    }
+
+    /// Prints the lines `labels` cover with each label underlined below them, `^` for the
+    /// primary label and `-` for secondary ones. Lines in the middle of long spans are left out.
+    fn snippet(&self, labels: &[&Label], severity: Severity, gutter: &str) {
+        let bar = Blue.bold().paint("|");
+
+        let mut printed: Option<usize> = None;
+        for label in labels {
+            let first = self.line(label.span.start);
+            let last = self
+                .line(label.span.end.max(label.span.start + 1) - 1)
+                .max(first);
+
+            for line in first..=last {
+                if last - first >= MAX_SPAN_LINES && line > first + 1 && line < last {
+                    continue;
+                }
+
+                if printed.is_none_or(|p| p < line) {
+                    if printed.is_some_and(|p| p + 1 < line) {
+                        println!("{}", Blue.bold().paint("..."));
+                    }
+                    println!(
+                        "{} {} {}",
+                        Blue.bold().paint(format!("{:>1$}", line + 1, gutter.len())),
+                        bar,
+                        self.line_text(line)
+                    );
+                    printed = Some(line);
+                }
+
+                let text = self.line_text(line);
+                let line_start = self.starts[line];
+                let from = if line == first {
+                    label.span.start - line_start
+                } else {
+                    text.len() - text.trim_start().len()
+                };
+                let to = if line == last {
+                    (label.span.end - line_start).min(text.len())
+                } else {
+                    text.len()
+                };
+
+                // Blank lines in the middle of a span have nothing to underline
+                if from >= to && line != first && line != last {
+                    continue;
+                }
+
+                let indent = text[..from].chars().count();
+                let width = text[from..to.max(from)].chars().count().max(1);
+                let (mark, style) = if label.primary {
+                    ("^", severity.style())
+                } else {
+                    ("-", Blue.bold())
+                };
+                let message = if line == last { &label.message[..] } else { "" };
+                println!(
+                    "{} {} {}{}",
+                    gutter,
+                    bar,
+                    " ".repeat(indent),
+                    style.paint(format!("{} {}", mark.repeat(width), message).trim_end())
+                );
+            }
+        }
+    }
+}
+
+/// Prints the lines of `file` that `span` covers with it underlined, without a message.
+pub(crate) fn snippet(file: &str, span: &Span) {
+    let source = Source::new(file);
+    if !source.contains(span) {
+        return;
+    }
+
+    let label = Label {
+        span: span.clone(),
+        message: String::new(),
+        primary: true,
+    };
+    let gutter = " ".repeat((source.line(span.end) + 1).to_string().len());
+    source.snippet(&[&label], Severity::Error, &gutter);
+}
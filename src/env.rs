@@ -1,11 +1,14 @@
-use std::collections::HashMap;
+use std::{cmp::Ordering, collections::HashMap, rc::Rc};
 
 use lasso::{Rodeo, Spur};
 
 use crate::{
+    diagnostic::Code,
     error::{Error, Result},
-    interpreter::{Value, ValueKind},
+    interpreter::{Context, List, Tail, Value, ValueKind},
+    native::IntoNative,
     parser::Expression,
+    types::{Annotation, Scheme},
 };
 
 pub type Symbol = Spur;
@@ -29,6 +32,38 @@ impl IntoSymbol for Symbol {
 pub struct Environment {
     symbol_store: Rodeo<Symbol>,
     funcs: HashMap<Symbol, Function>,
+    operators: HashMap<Symbol, Operator>,
+    /// What the environment looked like before the changes since [`Environment::begin`].
+    journal: Option<Journal>,
+}
+
+/// The functions replaced since a journal was started, each with what it replaced, and the
+/// operators from before it.
+struct Journal {
+    funcs: Vec<(Symbol, Option<Function>)>,
+    operators: HashMap<Symbol, Operator>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
+    /// Chaining the operator without parentheses is an error, as for comparisons.
+    None,
+}
+
+/// How a function of two arguments written between them in braces, like `{1 + 2}`, groups with
+/// the operators around it. Higher precedence binds tighter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Operator {
+    pub precedence: u8,
+    pub associativity: Associativity,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Environment {
@@ -36,18 +71,96 @@ impl Environment {
         Self {
             symbol_store: Rodeo::new(),
             funcs: HashMap::new(),
+            operators: HashMap::new(),
+            journal: None,
         }
     }
 
     pub fn insert_function(&mut self, name: &str, func: Function) {
-        self.funcs
-            .insert(self.symbol_store.get_or_intern(name), func);
+        let symbol = self.symbol_store.get_or_intern(name);
+        let old = self.funcs.insert(symbol, func);
+        if let Some(journal) = &mut self.journal {
+            if !journal.funcs.iter().any(|(s, _)| *s == symbol) {
+                journal.funcs.push((symbol, old));
+            }
+        }
+    }
+
+    /// Starts keeping track of the functions and operators that change, so that they can be put
+    /// back with [`Environment::rollback`] if loading a file fails halfway through.
+    pub fn begin(&mut self) {
+        self.journal = Some(Journal {
+            funcs: vec![],
+            operators: self.operators.clone(),
+        });
+    }
+
+    /// Keeps the changes since [`Environment::begin`].
+    pub fn commit(&mut self) {
+        self.journal = None;
+    }
+
+    /// Undoes the changes since [`Environment::begin`].
+    pub fn rollback(&mut self) {
+        let Some(journal) = self.journal.take() else {
+            return;
+        };
+        for (symbol, old) in journal.funcs.into_iter().rev() {
+            match old {
+                Some(func) => self.funcs.insert(symbol, func),
+                None => self.funcs.remove(&symbol),
+            };
+        }
+        self.operators = journal.operators;
+    }
+
+    /// Registers a host function that may capture state, unlike the builtin [`SystemFunction`]s.
+    pub fn register_native(&mut self, name: &str, args: usize, func: NativeFunction) {
+        self.insert_function(name, Function::new(args, func));
+    }
+
+    /// Registers a host function that receives its arguments unevaluated, like `if`.
+    pub fn register_lazy_native(&mut self, name: &str, args: usize, func: LazyNativeFunction) {
+        self.insert_function(name, Function::new(args, func));
+    }
+
+    /// Registers a Rust closure whose arguments and return value are converted from and into
+    /// [`Value`]s automatically, e.g. `env.register("inc", |n: i64| Ok(n + 1))`.
+    pub fn register<Args, F: IntoNative<Args>>(&mut self, name: &str, func: F) {
+        self.register_native(name, F::ARGS, func.into_native());
+    }
+
+    /// Lets a function of two arguments be written between them in braces.
+    pub fn set_operator(&mut self, name: &str, operator: Operator) {
+        self.operators
+            .insert(self.symbol_store.get_or_intern(name), operator);
+    }
+
+    pub fn operator<I: IntoSymbol>(&self, name: I) -> Option<Operator> {
+        let symbol = name.into_symbol(self)?;
+        self.operators.get(&symbol).copied()
     }
 
     pub fn get_symbol(&self, name: &str) -> Option<Symbol> {
         self.symbol_store.get(name)
     }
 
+    pub fn get_name(&self, symbol: Symbol) -> &str {
+        self.symbol_store.resolve(&symbol)
+    }
+
+    pub fn functions(&self) -> impl Iterator<Item = (Symbol, &Function)> {
+        self.funcs.iter().map(|(symbol, func)| (*symbol, func))
+    }
+
+    /// Names of every function in the environment, leaving out names that were only interned,
+    /// such as operators declared for functions that don't exist.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.funcs
+            .keys()
+            .map(|symbol| self.symbol_store.resolve(symbol))
+    }
+
     pub fn get_function<I: IntoSymbol>(&self, name: I) -> Option<&Function> {
         let symbol = name.into_symbol(self)?;
         self.funcs.get(&symbol)
@@ -71,24 +184,43 @@ impl Environment {
 pub struct Function {
     args: usize,
     body: FunctionBody,
+    signature: Option<Scheme>,
+    annotation: Option<Annotation>,
 }
 
 impl std::fmt::Debug for Function {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Function")
             .field("args", &self.args)
+            .field("signature", &self.signature)
+            .field("annotation", &self.annotation)
             .finish()
     }
 }
 
-impl<'a> Function {
+impl Function {
     pub fn new(args: usize, body: impl Into<FunctionBody>) -> Self {
         Self {
             args,
             body: body.into(),
+            signature: None,
+            annotation: None,
         }
     }
 
+    /// Declares the type of the function, which the type checker uses instead of inferring one.
+    pub fn with_signature(mut self, signature: Scheme) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+
+    /// Attaches the types written on the declaration of the function, which are checked whenever
+    /// it is called.
+    pub fn with_annotation(mut self, annotation: Annotation) -> Self {
+        self.annotation = Some(annotation);
+        self
+    }
+
     pub fn args(&self) -> usize {
         self.args
     }
@@ -96,12 +228,22 @@ impl<'a> Function {
     pub fn body(&self) -> &FunctionBody {
         &self.body
     }
+
+    pub fn signature(&self) -> Option<&Scheme> {
+        self.signature.as_ref()
+    }
+
+    pub fn annotation(&self) -> Option<&Annotation> {
+        self.annotation.as_ref()
+    }
 }
 
 pub enum FunctionBody {
     Normal(Expression),
     System(SystemFunction),
     LazySystem(LazySystemFunction),
+    Native(NativeFunction),
+    LazyNative(LazyNativeFunction),
 }
 
 impl From<Expression> for FunctionBody {
@@ -122,13 +264,23 @@ impl From<LazySystemFunction> for FunctionBody {
     }
 }
 
+impl From<NativeFunction> for FunctionBody {
+    fn from(func: NativeFunction) -> Self {
+        Self::Native(func)
+    }
+}
+
+impl From<LazyNativeFunction> for FunctionBody {
+    fn from(func: LazyNativeFunction) -> Self {
+        Self::LazyNative(func)
+    }
+}
+
 pub type SystemFunction = fn(&[Value]) -> Result<Value>;
-pub type LazySystemFunction = fn(
-    &[Expression],
-    fn(&Expression, &Environment, &Vec<Value>) -> Result<Value>,
-    &Environment,
-    &Vec<Value>,
-) -> Result<Value>;
+pub type LazySystemFunction = fn(&[Expression], &mut Context, &[Value]) -> Result<Tail>;
+
+pub type NativeFunction = Box<dyn Fn(&[Value]) -> Result<Value>>;
+pub type LazyNativeFunction = Box<dyn Fn(&[Expression], &mut Context, &[Value]) -> Result<Tail>>;
 
 macro_rules! extract_args {
     ($params:expr,$($variant:ident),+) => {{
@@ -140,22 +292,201 @@ macro_rules! extract_args {
         (
             $(
                 if let Value::$variant(x) = $params[next() - 1].clone() { x } else {
-                    return Err(Error::General(format!("wrong argument type for index {}", counter)));
+                    return Err(Error::coded(Code::WrongType, format!(
+                        "wrong argument type for index {}: expected {}, found {}",
+                        counter,
+                        ValueKind::$variant,
+                        ValueKind::from(&$params[counter - 1])
+                    )));
                 },
             )+
         )
     }};
 }
 
+/// Converts both operands of a numeric builtin to floats, or reports which kinds were found.
+fn numbers(name: &str, args: &[Value]) -> Result<(f64, f64)> {
+    match (args[0].as_float(), args[1].as_float()) {
+        (Some(lhs), Some(rhs)) => Ok((lhs, rhs)),
+        _ => Err(Error::coded(
+            Code::WrongType,
+            format!(
+                "{name} expects two numbers, found {} and {}",
+                ValueKind::from(&args[0]),
+                ValueKind::from(&args[1])
+            ),
+        )),
+    }
+}
+
+/// Applies `int` if both operands are integers and `float` otherwise, so integers are only
+/// promoted when mixed with floats.
+fn arithmetic(
+    name: &str,
+    args: &[Value],
+    int: fn(i64, i64) -> Option<i64>,
+    float: fn(f64, f64) -> f64,
+) -> Result<Value> {
+    match (&args[0], &args[1]) {
+        (Value::Int(lhs), Value::Int(rhs)) => int(*lhs, *rhs)
+            .map(Value::Int)
+            .ok_or_else(|| Error::coded(Code::Arithmetic, format!("integer overflow in {name}"))),
+        _ => {
+            let (lhs, rhs) = numbers(name, args)?;
+            Ok(Value::Float(float(lhs, rhs)))
+        }
+    }
+}
+
+/// Compares two values with `test` on their order. Floats are compared as they are rather than by
+/// [`order`], so that nothing is less than, greater than or equal to NaN.
+fn compare(args: &[Value], test: fn(Ordering) -> bool) -> Result<Value> {
+    let ordering = match (&args[0], &args[1]) {
+        (Value::Int(lhs), Value::Int(rhs)) => Some(lhs.cmp(rhs)),
+        (lhs, rhs) => match (lhs.as_float(), rhs.as_float()) {
+            (Some(lhs), Some(rhs)) => lhs.partial_cmp(&rhs),
+            _ => Some(order(lhs, rhs)?),
+        },
+    };
+    Ok(Value::Bool(ordering.is_some_and(test)))
+}
+
+fn incomparable(a: &Value, b: &Value) -> Error {
+    Error::coded(
+        Code::WrongType,
+        format!(
+            "cannot compare {} and {}",
+            ValueKind::from(a),
+            ValueKind::from(b)
+        ),
+    )
+}
+
+fn nonzero_divisor(args: &[Value]) -> Result<()> {
+    match args[1] {
+        Value::Int(0) | Value::Float(0.0) => {
+            Err(Error::coded(Code::Arithmetic, "division by zero"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Converts a count or position given to a list builtin, which can't be negative.
+fn index(name: &str, n: i64) -> Result<usize> {
+    usize::try_from(n).map_err(|_| {
+        Error::coded(
+            Code::IndexOutOfBounds,
+            format!("{name} expects a non-negative index, found {n}"),
+        )
+    })
+}
+
+/// The chars of `s` from index `start` up to but not including `end`.
+fn substring(s: &str, start: usize, end: usize) -> Result<Value> {
+    let len = s.chars().count();
+    if start > end || end > len {
+        return Err(Error::coded(
+            Code::IndexOutOfBounds,
+            format!("range {start}..{end} is out of bounds for a string of length {len}"),
+        ));
+    }
+    Ok(Value::String(
+        s.chars()
+            .skip(start)
+            .take(end - start)
+            .collect::<String>()
+            .into(),
+    ))
+}
+
+fn string(s: impl Into<Rc<str>>) -> Value {
+    Value::String(s.into())
+}
+
+/// Equality as `=` sees it, where numbers are equal by value whether they are integers or floats.
+/// Values of different kinds can't be compared at all.
+fn equal(a: &Value, b: &Value) -> Result<bool> {
+    match (a, b) {
+        (Value::Int(a), Value::Int(b)) => Ok(a == b),
+        (Value::List(a), Value::List(b)) => {
+            for (a, b) in a.iter().zip(b) {
+                if !equal(a, b)? {
+                    return Ok(false);
+                }
+            }
+            Ok(a.len() == b.len())
+        }
+        (a, b) => match (a.as_float(), b.as_float()) {
+            (Some(a), Some(b)) => Ok(a == b),
+            _ if ValueKind::from(a) == ValueKind::from(b) => Ok(a == b),
+            _ => Err(incomparable(a, b)),
+        },
+    }
+}
+
+/// The order of two values of the same kind. Strings and lists are ordered lexicographically,
+/// `false` comes before `true`, and functions have no order.
+fn order(a: &Value, b: &Value) -> Result<Ordering> {
+    match (a, b) {
+        (Value::Int(a), Value::Int(b)) => Ok(a.cmp(b)),
+        (Value::String(a), Value::String(b)) => Ok(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Ok(a.cmp(b)),
+        (Value::Nothing, Value::Nothing) => Ok(Ordering::Equal),
+        (Value::List(a), Value::List(b)) => {
+            for (a, b) in a.iter().zip(b) {
+                match order(a, b)? {
+                    Ordering::Equal => {}
+                    ordering => return Ok(ordering),
+                }
+            }
+            Ok(a.len().cmp(&b.len()))
+        }
+        (Value::Function(_), Value::Function(_)) => {
+            Err(Error::coded(Code::WrongType, "functions cannot be ordered"))
+        }
+        (a, b) => match (a.as_float(), b.as_float()) {
+            (Some(a), Some(b)) => Ok(a.total_cmp(&b)),
+            _ => Err(incomparable(a, b)),
+        },
+    }
+}
+
+/// Evaluates the operand of a boolean builtin, which must be a bool.
+fn condition(name: &str, param: &Expression, ctx: &mut Context, args: &[Value]) -> Result<bool> {
+    match ctx.eval(param, args)? {
+        Value::Bool(b) => Ok(b),
+        value => Err(Error::coded(
+            Code::WrongType,
+            format!("{name} expects a bool, found {}", ValueKind::from(&value)),
+        )
+        .at(param.span())),
+    }
+}
+
+/// The builtins that can be written between their arguments, loosely following Haskell.
+const OPERATORS: [(&[&str], u8, Associativity); 5] = [
+    (&["or"], 2, Associativity::Right),
+    (&["and"], 3, Associativity::Right),
+    (&["=", "!=", "<", "<=", ">", ">="], 4, Associativity::None),
+    (&["+", "-"], 6, Associativity::Left),
+    (&["*", "/", "//", "%"], 7, Associativity::Left),
+];
+
 macro_rules! default_env {
-    ($(($t:ident,$name:literal,$num_args:literal,$func:tt)),+) => {
+    ($(($t:ident,$name:literal,$num_args:literal,$signature:literal,$func:tt)),+) => {
         pub fn default_env() -> Environment {
             let mut env = Environment::new();
             $(
                 #[allow(unused_parens)]
                 let func: $t = $func;
-                env.insert_function($name, Function::new($num_args, func));
+                let signature = $signature.parse().expect("invalid builtin signature");
+                env.insert_function($name, Function::new($num_args, func).with_signature(signature));
             )+
+            for (names, precedence, associativity) in OPERATORS {
+                for name in names {
+                    env.set_operator(name, Operator { precedence, associativity });
+                }
+            }
             env
         }
     };
@@ -163,87 +494,297 @@ macro_rules! default_env {
 
 #[rustfmt::skip]
 default_env![
-    (SystemFunction, "print", 1, (|args| {
+    (SystemFunction, "print", 1, "a -> Nothing", (|args| {
         println!("{}", args[0]);
         Ok(Value::Nothing)
     })),
-    (SystemFunction, "true", 0, (|_| {
+    (SystemFunction, "true", 0, "Bool", (|_| {
         Ok(Value::Bool(true))
     })),
-    (SystemFunction, "false", 0, (|_| {
+    (SystemFunction, "false", 0, "Bool", (|_| {
        Ok(Value::Bool(false))
     })),
-    (SystemFunction, "+", 2, (|args| {
+    (SystemFunction, "+", 2, "a -> a -> a where a: Num | String", (|args| {
         match (&args[0], &args[1]) {
-            (Value::Num(a), Value::Num(b)) => Ok(Value::Num(a + b)),
-            (Value::String(a), Value::String(b)) => Ok(Value::String(a.to_owned() + b)),
-            (a, b) => Err(Error::General(format!("types {} and {} cannot be added together", ValueKind::from(a), ValueKind::from(b))))
+            (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{a}{b}").into())),
+            (a, b) if a.as_float().is_some() && b.as_float().is_some() => {
+                arithmetic("+", args, i64::checked_add, |a, b| a + b)
+            }
+            (a, b) => Err(Error::coded(Code::WrongType, format!("types {} and {} cannot be added together", ValueKind::from(a), ValueKind::from(b))))
         }
     })),
-    (SystemFunction, "-", 2, (|args| {
-        let (lhs, rhs) = extract_args!(args, Num, Num);
-
-        Ok(Value::Num(lhs - rhs))
+    (SystemFunction, "-", 2, "Num -> Num -> Num", (|args| {
+        arithmetic("-", args, i64::checked_sub, |a, b| a - b)
     })),
-    (SystemFunction, "*", 2, (|args| {
-        let (lhs, rhs) = extract_args!(args, Num, Num);
-
-        Ok(Value::Num(lhs * rhs))
+    (SystemFunction, "*", 2, "Num -> Num -> Num", (|args| {
+        arithmetic("*", args, i64::checked_mul, |a, b| a * b)
     })),
-    (SystemFunction, "/", 2, (|args| {
-        let (lhs, rhs) = extract_args!(args, Num, Num);
+    (SystemFunction, "/", 2, "Num -> Num -> Num", (|args| {
+        let (lhs, rhs) = numbers("/", args)?;
+        nonzero_divisor(args)?;
 
-        Ok(Value::Num(lhs / rhs))
+        Ok(Value::Float(lhs / rhs))
     })),
-    (SystemFunction, "%", 2, (|args| {
-        let (lhs, rhs) = extract_args!(args, Num, Num);
+    (SystemFunction, "//", 2, "Num -> Num -> Num", (|args| {
+        numbers("//", args)?;
+        nonzero_divisor(args)?;
 
-        Ok(Value::Num(lhs % rhs))
+        arithmetic("//", args, i64::checked_div_euclid, f64::div_euclid)
     })),
-    (SystemFunction, "<", 2, (|args| {
-        let (lhs, rhs) = extract_args!(args, Num, Num);
+    (SystemFunction, "%", 2, "Num -> Num -> Num", (|args| {
+        numbers("%", args)?;
+        nonzero_divisor(args)?;
 
-        Ok(Value::Bool(lhs < rhs))
+        arithmetic("%", args, i64::checked_rem_euclid, f64::rem_euclid)
     })),
-    (SystemFunction, ">", 2, (|args| {
-        let (lhs, rhs) = extract_args!(args, Num, Num);
-
-        Ok(Value::Bool(lhs > rhs))
+    (SystemFunction, "<", 2, "a -> a -> Bool", (|args| {
+        compare(args, Ordering::is_lt)
     })),
-    (LazySystemFunction, "if", 3, (|params, eval, env, args| {
-        let pred = extract_args!(&[eval(&params[0], env, &args)?], Bool);
+    (SystemFunction, "<=", 2, "a -> a -> Bool", (|args| {
+        compare(args, Ordering::is_le)
+    })),
+    (SystemFunction, ">", 2, "a -> a -> Bool", (|args| {
+        compare(args, Ordering::is_gt)
+    })),
+    (SystemFunction, ">=", 2, "a -> a -> Bool", (|args| {
+        compare(args, Ordering::is_ge)
+    })),
+    (LazySystemFunction, "if", 3, "Bool -> a -> a -> a", (|params, ctx, args| {
+        let pred = extract_args!(&[ctx.eval(&params[0], args)?], Bool);
 
-        Ok(if pred.0 {
-            eval(&params[1], env, &args)?
+        Ok(Tail::Eval(if pred.0 {
+            params[1].clone()
         } else {
-            eval(&params[2], env, &args)?
+            params[2].clone()
+        }))
+    })),
+    (SystemFunction, "=", 2, "a -> a -> Bool", (|args| {
+        Ok(Value::Bool(equal(&args[0], &args[1])?))
+    })),
+    (SystemFunction, "!=", 2, "a -> a -> Bool", (|args| {
+        Ok(Value::Bool(!equal(&args[0], &args[1])?))
+    })),
+    (LazySystemFunction, "and", 2, "Bool -> Bool -> Bool", (|params, ctx, args| {
+        Ok(if condition("and", &params[0], ctx, args)? {
+            Tail::Eval(params[1].clone())
+        } else {
+            Tail::Value(Value::Bool(false))
         })
     })),
-    (SystemFunction, "=", 2, (|args| {
-        let (lhs, rhs) = extract_args!(args, Num, Num);
-
-        Ok(Value::Bool(lhs == rhs))
+    (LazySystemFunction, "or", 2, "Bool -> Bool -> Bool", (|params, ctx, args| {
+        Ok(if condition("or", &params[0], ctx, args)? {
+            Tail::Value(Value::Bool(true))
+        } else {
+            Tail::Eval(params[1].clone())
+        })
+    })),
+    (SystemFunction, "not", 1, "Bool -> Bool", (|args| {
+        let b = extract_args!(args, Bool).0;
+        Ok(Value::Bool(!b))
+    })),
+    (SystemFunction, "xor", 2, "Bool -> Bool -> Bool", (|args| {
+        let (a, b) = extract_args!(args, Bool, Bool);
+        Ok(Value::Bool(a != b))
     })),
-    (SystemFunction, "none", 0, (|_| {
+    (SystemFunction, "none", 0, "Nothing", (|_| {
         Ok(Value::Nothing)
     })),
-    (SystemFunction, "pair", 2, (|args| {
-        Ok(Value::List(Vec::from([args[0].clone(), args[1].clone()])))
+    (SystemFunction, "pair", 2, "a -> a -> List a", (|args| {
+        Ok(Value::List(List::from(vec![args[0].clone(), args[1].clone()])))
     })),
-    (SystemFunction, "head", 1, (|args| {
+    (SystemFunction, "head", 1, "List a -> a", (|args| {
         let list = extract_args!(args, List).0;
-        Ok(list[0].clone())
+        list.front().cloned().ok_or_else(|| Error::coded(Code::EmptyList, "head of an empty list"))
     })),
-    (SystemFunction, "tail", 1, (|args| {
+    (SystemFunction, "tail", 1, "List a -> List a", (|args| {
         let list = extract_args!(args, List).0;
-        Ok(Value::List(list[1..].iter().cloned().collect()))
+        if list.is_empty() {
+            return Err(Error::coded(Code::EmptyList, "tail of an empty list"));
+        }
+        Ok(Value::List(list.skip(1)))
     })),
-    (SystemFunction, "fuse", 2, (|args| {
+    (SystemFunction, "fuse", 2, "List a -> List a -> List a", (|args| {
         match (args[0].clone(), args[1].clone()) {
-            (Value::List(mut x), Value::List(mut y)) => Ok(Value::List({ x.append(&mut y); x })),
-            (Value::List(mut x), y) => Ok(Value::List({ x.push(y); x })),
-            (x, Value::List(mut y)) => Ok(Value::List({ let mut v = vec![x]; v.append(&mut y); v })),
-            (x, y) => Ok(Value::List(vec![x, y])),
+            (Value::List(mut x), Value::List(y)) => Ok(Value::List({ x.append(y); x })),
+            (Value::List(mut x), y) => Ok(Value::List({ x.push_back(y); x })),
+            (x, Value::List(mut y)) => Ok(Value::List({ y.push_front(x); y })),
+            (x, y) => Ok(Value::List(List::from(vec![x, y]))),
+        }
+    })),
+    (SystemFunction, "push", 2, "List a -> a -> List a", (|args| {
+        let mut list = extract_args!(args, List).0;
+        list.push_back(args[1].clone());
+        Ok(Value::List(list))
+    })),
+    (SystemFunction, "len", 1, "List a -> Num", (|args| {
+        let list = extract_args!(args, List).0;
+        Ok(Value::Int(list.len() as i64))
+    })),
+    (SystemFunction, "empty?", 1, "List a -> Bool", (|args| {
+        let list = extract_args!(args, List).0;
+        Ok(Value::Bool(list.is_empty()))
+    })),
+    (SystemFunction, "nth", 2, "List a -> Num -> a", (|args| {
+        let (list, n) = extract_args!(args, List, Int);
+        let i = index("nth", n)?;
+        list.get(i).cloned().ok_or_else(|| Error::coded(
+            Code::IndexOutOfBounds,
+            format!("index {i} is out of bounds for a list of length {}", list.len()),
+        ))
+    })),
+    (SystemFunction, "reverse", 1, "List a -> List a", (|args| {
+        let list = extract_args!(args, List).0;
+        Ok(Value::List(list.into_iter().rev().collect()))
+    })),
+    (SystemFunction, "range", 2, "Num -> Num -> List Num", (|args| {
+        let (start, end) = extract_args!(args, Int, Int);
+        Ok(Value::List((start..end).map(Value::Int).collect()))
+    })),
+    (SystemFunction, "take", 2, "List a -> Num -> List a", (|args| {
+        let (list, n) = extract_args!(args, List, Int);
+        Ok(Value::List(list.take(index("take", n)?.min(list.len()))))
+    })),
+    (SystemFunction, "drop", 2, "List a -> Num -> List a", (|args| {
+        let (list, n) = extract_args!(args, List, Int);
+        Ok(Value::List(list.skip(index("drop", n)?.min(list.len()))))
+    })),
+    (SystemFunction, "concat", 2, "List a -> List a -> List a", (|args| {
+        let (mut list, other) = extract_args!(args, List, List);
+        list.append(other);
+        Ok(Value::List(list))
+    })),
+    (SystemFunction, "sort", 1, "List a -> List a", (|args| {
+        let mut items = extract_args!(args, List).0.into_iter().collect::<Vec<_>>();
+        let mut error = None;
+        items.sort_by(|a, b| order(a, b).unwrap_or_else(|e| {
+            error.get_or_insert(e);
+            Ordering::Equal
+        }));
+        match error {
+            Some(error) => Err(error),
+            None => Ok(Value::List(items.into())),
+        }
+    })),
+    (SystemFunction, "zip", 2, "List a -> List a -> List (List a)", (|args| {
+        let (list, other) = extract_args!(args, List, List);
+        Ok(Value::List(list.into_iter().zip(other).map(|(a, b)| Value::List(List::from(vec![a, b]))).collect()))
+    })),
+    (SystemFunction, "contains", 2, "List a -> a -> Bool", (|args| {
+        let list = extract_args!(args, List).0;
+        Ok(Value::Bool(list.iter().any(|item| equal(item, &args[1]).unwrap_or(false))))
+    })),
+    (SystemFunction, "flatten", 1, "List (List a) -> List a", (|args| {
+        let mut flat = List::new();
+        for item in extract_args!(args, List).0 {
+            match item {
+                Value::List(inner) => flat.append(inner),
+                item => return Err(Error::coded(Code::WrongType, format!(
+                    "flatten expects a list of lists, found an element of type {}",
+                    ValueKind::from(&item)
+                ))),
+            }
+        }
+        Ok(Value::List(flat))
+    })),
+    (SystemFunction, "dedup", 1, "List a -> List a", (|args| {
+        let mut unique = List::new();
+        for item in extract_args!(args, List).0 {
+            if !unique.iter().any(|seen| equal(seen, &item).unwrap_or(false)) {
+                unique.push_back(item);
+            }
+        }
+        Ok(Value::List(unique))
+    })),
+    (SystemFunction, "index-of", 2, "List a -> a -> Num", (|args| {
+        let list = extract_args!(args, List).0;
+        let position = list.iter().position(|item| equal(item, &args[1]).unwrap_or(false));
+        Ok(Value::Int(position.map_or(-1, |i| i as i64)))
+    })),
+    (SystemFunction, "str-len", 1, "String -> Num", (|args| {
+        let s = extract_args!(args, String).0;
+        Ok(Value::Int(s.chars().count() as i64))
+    })),
+    (SystemFunction, "substring", 3, "String -> Num -> Num -> String", (|args| {
+        let (s, start, end) = extract_args!(args, String, Int, Int);
+        substring(&s, index("substring", start)?, index("substring", end)?)
+    })),
+    (SystemFunction, "char-at", 2, "String -> Num -> String", (|args| {
+        let (s, i) = extract_args!(args, String, Int);
+        let i = index("char-at", i)?;
+        s.chars().nth(i).map(|c| string(c.to_string())).ok_or_else(|| Error::coded(
+            Code::IndexOutOfBounds,
+            format!("index {i} is out of bounds for a string of length {}", s.chars().count()),
+        ))
+    })),
+    (SystemFunction, "chars", 1, "String -> List String", (|args| {
+        let s = extract_args!(args, String).0;
+        Ok(Value::List(s.chars().map(|c| string(c.to_string())).collect()))
+    })),
+    (SystemFunction, "split", 2, "String -> String -> List String", (|args| {
+        let (s, separator) = extract_args!(args, String, String);
+        if separator.is_empty() {
+            return Ok(Value::List(s.chars().map(|c| string(c.to_string())).collect()));
+        }
+        Ok(Value::List(s.split(&*separator).map(string).collect()))
+    })),
+    (SystemFunction, "join", 2, "List String -> String -> String", (|args| {
+        let (list, separator) = extract_args!(args, List, String);
+        let mut joined = String::new();
+        for (i, item) in list.iter().enumerate() {
+            let Value::String(item) = item else {
+                return Err(Error::coded(Code::WrongType, format!(
+                    "join expects a list of strings, found an element of type {}",
+                    ValueKind::from(item)
+                )));
+            };
+            if i > 0 {
+                joined.push_str(&separator);
+            }
+            joined.push_str(item);
+        }
+        Ok(string(joined))
+    })),
+    (SystemFunction, "trim", 1, "String -> String", (|args| {
+        let s = extract_args!(args, String).0;
+        Ok(string(s.trim()))
+    })),
+    (SystemFunction, "upper", 1, "String -> String", (|args| {
+        let s = extract_args!(args, String).0;
+        Ok(string(s.to_uppercase()))
+    })),
+    (SystemFunction, "lower", 1, "String -> String", (|args| {
+        let s = extract_args!(args, String).0;
+        Ok(string(s.to_lowercase()))
+    })),
+    (SystemFunction, "str-contains", 2, "String -> String -> Bool", (|args| {
+        let (s, pattern) = extract_args!(args, String, String);
+        Ok(Value::Bool(s.contains(&*pattern)))
+    })),
+    (SystemFunction, "starts-with", 2, "String -> String -> Bool", (|args| {
+        let (s, prefix) = extract_args!(args, String, String);
+        Ok(Value::Bool(s.starts_with(&*prefix)))
+    })),
+    (SystemFunction, "ends-with", 2, "String -> String -> Bool", (|args| {
+        let (s, suffix) = extract_args!(args, String, String);
+        Ok(Value::Bool(s.ends_with(&*suffix)))
+    })),
+    (SystemFunction, "replace", 3, "String -> String -> String -> String", (|args| {
+        let (s, from, to) = extract_args!(args, String, String, String);
+        Ok(string(s.replace(&*from, &to)))
+    })),
+    (SystemFunction, "str->num", 1, "String -> List Num", (|args| {
+        // Failing to parse isn't an error, so the number comes in a list that is empty if it failed
+        let s = extract_args!(args, String).0;
+        let n = match s.parse::<i64>() {
+            Ok(n) => Some(Value::Int(n)),
+            Err(_) => s.parse::<f64>().ok().filter(|n| n.is_finite()).map(Value::Float),
+        };
+        Ok(Value::List(n.into_iter().collect()))
+    })),
+    (SystemFunction, "num->str", 1, "Num -> String", (|args| {
+        match &args[0] {
+            n @ (Value::Int(_) | Value::Float(_)) => Ok(string(n.to_string())),
+            n => Err(Error::coded(Code::WrongType, format!("num->str expects a number, found {}", ValueKind::from(n)))),
         }
     }))
 ];
@@ -1,6 +1,9 @@
 use ansi_term::Color::{Blue, Red};
 
-use crate::span::Span;
+use crate::{
+    diagnostic::{snippet, Code, Diagnostic},
+    span::{SourceMap, Span},
+};
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -8,40 +11,177 @@ pub type Result<T> = core::result::Result<T, Error>;
 pub enum Error {
     General(String),
     Spanned(String, Span),
+    Diagnostic(Box<Diagnostic>),
+    /// An error raised at runtime, with the calls it happened in, most recent call last.
+    Traced(Box<Error>, Vec<TraceFrame>),
+    /// Several errors found in one pass, such as every declaration of a file that failed to parse.
+    Multiple(Vec<Error>),
+}
+
+impl From<Diagnostic> for Error {
+    fn from(diagnostic: Diagnostic) -> Self {
+        Self::Diagnostic(Box::new(diagnostic))
+    }
+}
+
+/// A call in the trace of a runtime error, or a run of identical calls collapsed into one.
+#[derive(Debug, Clone)]
+pub enum TraceFrame {
+    Call {
+        function: String,
+        /// Where the function was called from, `None` for calls made by the host.
+        call_site: Option<Span>,
+        repeated: usize,
+    },
+    /// Calls left out of the middle of a trace that was too long.
+    Omitted(usize),
 }
 
 impl Error {
+    /// An error without a location, with a stable code.
+    pub fn coded(code: Code, message: impl Into<String>) -> Self {
+        Diagnostic::error(message).with_code(code).into()
+    }
+
+    /// Combines the errors of a pass into one, `None` if there weren't any.
+    pub fn many(mut errors: Vec<Error>) -> Option<Self> {
+        match errors.len() {
+            0 => None,
+            1 => errors.pop(),
+            _ => Some(Self::Multiple(errors)),
+        }
+    }
+
+    /// Points an error without a location at `span`, leaving located and traced errors alone.
+    pub fn at(self, span: Span) -> Self {
+        match self {
+            Self::General(msg) => Self::Spanned(msg, span),
+            Self::Diagnostic(diagnostic) if diagnostic.primary_span().is_none() => {
+                diagnostic.with_label(span, "").into()
+            }
+            error => error,
+        }
+    }
+
+    /// Adds to the diagnostic of an error, leaving errors without one alone.
+    pub fn map_diagnostic(self, f: impl FnOnce(Diagnostic) -> Diagnostic) -> Self {
+        match self {
+            Self::Diagnostic(diagnostic) => f(*diagnostic).into(),
+            error => error,
+        }
+    }
+
+    /// The error with every location moved `offset` bytes later, for a source that doesn't start
+    /// at zero.
+    pub fn offset(self, offset: usize) -> Self {
+        let moved = |span: Span| span.start + offset..span.end + offset;
+        match self {
+            Self::General(msg) => Self::General(msg),
+            Self::Spanned(msg, span) => Self::Spanned(msg, moved(span)),
+            Self::Diagnostic(mut diagnostic) => {
+                for label in &mut diagnostic.labels {
+                    label.span = moved(label.span.clone());
+                }
+                Self::Diagnostic(diagnostic)
+            }
+            Self::Traced(error, mut trace) => {
+                for frame in &mut trace {
+                    if let TraceFrame::Call {
+                        call_site: Some(span),
+                        ..
+                    } = frame
+                    {
+                        *span = moved(span.clone());
+                    }
+                }
+                Self::Traced(Box::new(error.offset(offset)), trace)
+            }
+            Self::Multiple(errors) => {
+                Self::Multiple(errors.into_iter().map(|e| e.offset(offset)).collect())
+            }
+        }
+    }
+
+    /// Where the error happened, if it is known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::Spanned(_, span) => Some(span.clone()),
+            Self::Diagnostic(diagnostic) => diagnostic.primary_span().cloned(),
+            Self::Traced(error, _) => error.span(),
+            _ => None,
+        }
+    }
+
+    pub fn code(&self) -> Option<Code> {
+        match self {
+            Self::Diagnostic(diagnostic) => diagnostic.code,
+            Self::Traced(error, _) => error.code(),
+            _ => None,
+        }
+    }
+
     pub fn message(&self) -> &String {
         match self {
             Self::General(msg) | Self::Spanned(msg, _) => msg,
+            Self::Diagnostic(diagnostic) => &diagnostic.message,
+            Self::Traced(error, _) => error.message(),
+            Self::Multiple(errors) => errors[0].message(),
         }
     }
 
+    /// Prints the error with the lines of `file` it points at.
     pub fn log(&self, file: &str) {
-        println!("{}: {}", Red.bold().paint("error"), self.message());
-
-        if let Self::Spanned(_, span) = self {
-            let line_num = file[..span.start].chars().filter(|x| *x == '\n').count();
-            let offset = file[..span.start]
-                .chars()
-                .rev()
-                .enumerate()
-                .find(|(_, c)| *c == '\n')
-                .unwrap_or((span.start, '\n'))
-                .0;
-            let padding = ((((line_num as f64).log10()) as usize) + 4) + offset;
-
-            println!(
-                "{} {} {}",
-                Blue.bold().paint((line_num + 1).to_string()),
-                Blue.bold().paint("|"),
-                file.lines().nth(line_num).unwrap()
-            );
-            println!(
-                "{}{}",
-                " ".repeat(padding),
-                Red.bold().paint("^".repeat(span.len()))
-            );
+        self.log_in(&SourceMap::single(file));
+    }
+
+    /// Prints the error with each location shown in the source it points into, for errors that
+    /// span several sources, such as a call from the repl into a loaded file.
+    pub fn log_in(&self, sources: &SourceMap) {
+        match self {
+            Self::General(msg) => Diagnostic::error(msg).render_in(sources),
+            Self::Spanned(msg, span) => Diagnostic::error(msg)
+                .with_label(span.clone(), "")
+                .render_in(sources),
+            Self::Diagnostic(diagnostic) => diagnostic.render_in(sources),
+            Self::Traced(error, trace) => {
+                println!(
+                    "{}",
+                    Blue.bold().paint("traceback (most recent call last):")
+                );
+                for frame in trace {
+                    match frame {
+                        TraceFrame::Call {
+                            function,
+                            call_site,
+                            repeated,
+                        } => {
+                            match call_site.as_ref().and_then(|span| sources.find(span)) {
+                                Some((file, call_site)) => {
+                                    println!("  {} {}", function, Blue.bold().paint("called at"));
+                                    snippet(file, &call_site);
+                                }
+                                None => println!("  {function}"),
+                            }
+                            if *repeated > 1 {
+                                println!("  [repeated {} more times]", repeated - 1);
+                            }
+                        }
+                        TraceFrame::Omitted(calls) => println!("  ... {calls} more calls"),
+                    }
+                }
+                error.log_in(sources);
+            }
+            Self::Multiple(errors) => {
+                for error in errors {
+                    error.log_in(sources);
+                    println!();
+                }
+                println!(
+                    "{}: aborting due to {} previous errors",
+                    Red.bold().paint("error"),
+                    errors.len()
+                );
+            }
         }
     }
 }
@@ -57,4 +197,13 @@ impl<T> UnwrapPretty<T, &str> for Result<T> {
             std::process::exit(1);
         })
     }
-}
\ No newline at end of file
+}
+
+impl<T> UnwrapPretty<T, &SourceMap> for Result<T> {
+    fn unwrap_pretty(self, ctx: &SourceMap) -> T {
+        self.unwrap_or_else(|error| {
+            error.log_in(ctx);
+            std::process::exit(1);
+        })
+    }
+}
@@ -1,35 +1,102 @@
 use crate::{
-    env::{Environment, FunctionBody},
-    error::{Error, Result},
-    parser::Expression,
+    checker::{check, check_expr, infer},
+    diagnostic::{Code, Diagnostic},
+    env::{default_env, Environment, Function, FunctionBody, Symbol},
+    error::{Error, Result, TraceFrame},
+    parser::{parse_expr, parse_file, Expression, Lambda, Pattern},
+    span::{SourceMap, Span},
+    tokenizer::{tokenize, tokenize_expr, Token},
+    types::{Annotation, Scheme, Type},
 };
-use std::fmt;
+use im_rc::Vector;
+use std::{borrow::Cow, fmt, rc::Rc};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// A persistent list, whose clones share their elements so that taking the tail or adding to
+/// either end doesn't copy the rest.
+pub type List = Vector<Value>;
+
+/// A value of `f`. Strings and lists are shared rather than copied, so cloning any value is cheap,
+/// as happens every time a local is used.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
-    Num(u64),
-    String(String),
+    Int(i64),
+    Float(f64),
+    String(Rc<str>),
     Bool(bool),
-    List(Vec<Value>),
+    List(List),
+    Function(Rc<Callable>),
     Nothing,
 }
 
+/// A function value, created by referencing a declared function with `'name`, by a lambda or by
+/// applying a function to fewer arguments than it takes.
+#[derive(Debug)]
+pub enum Callable {
+    Named(Symbol),
+    Closure(Rc<Lambda>, Vec<Value>),
+    /// The arguments bound so far, with `None` for the ones still missing.
+    Partial(Rc<Callable>, Vec<Option<Value>>),
+}
+
+impl Callable {
+    pub fn arity(&self, env: &Environment) -> usize {
+        match self {
+            Self::Named(symbol) => env.get_function(*symbol).map_or(0, Function::args),
+            Self::Closure(lambda, _) => lambda.args,
+            Self::Partial(_, args) => args.iter().filter(|arg| arg.is_none()).count(),
+        }
+    }
+}
+
+/// Fills the missing arguments of a partial application from left to right.
+fn fill(bound: &[Option<Value>], args: Vec<Value>) -> Vec<Option<Value>> {
+    let mut args = args.into_iter();
+    bound
+        .iter()
+        .map(|arg| arg.clone().or_else(|| args.next()))
+        .collect()
+}
+
+impl PartialEq for Callable {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Named(a), Self::Named(b)) => a == b,
+            (Self::Closure(a, a_captured), Self::Closure(b, b_captured)) => {
+                Rc::ptr_eq(a, b) && a_captured == b_captured
+            }
+            (Self::Partial(a, a_args), Self::Partial(b, b_args)) => a == b && a_args == b_args,
+            _ => false,
+        }
+    }
+}
+
+impl Value {
+    /// Widens integers to floats, for arithmetic mixing the two.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Self::Int(n) => Some(*n as f64),
+            Self::Float(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::Num(n) => write!(f, "{}", n),
+            Self::Int(n) => write!(f, "{}", n),
+            Self::Float(n) => write!(f, "{:?}", n),
             Self::String(s) => write!(f, "{}", s),
             Self::Bool(b) => write!(f, "{}", b),
             Self::Nothing => write!(f, "none"),
+            Self::Function(_) => write!(f, "<function>"),
             Self::List(l) => {
                 write!(f, "[")?;
-                if let Some((tail, head)) = l.split_last() {
-                    for val in head {
-                        write!(f, "{}, ", val)?;
+                for (i, val) in l.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
                     }
-                    write!(f, "{}", tail)?;
-                } else {
-                    return write!(f, "]");
+                    write!(f, "{}", val)?;
                 }
                 write!(f, "]")
             }
@@ -39,21 +106,25 @@ impl fmt::Display for Value {
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ValueKind {
-    Num,
+    Int,
+    Float,
     String,
     Bool,
     Nothing,
     List,
+    Function,
 }
 
 impl From<&Value> for ValueKind {
     fn from(value: &Value) -> Self {
         match value {
-            Value::Num(_) => Self::Num,
+            Value::Int(_) => Self::Int,
+            Value::Float(_) => Self::Float,
             Value::String(_) => Self::String,
             Value::Bool(_) => Self::Bool,
             Value::Nothing => Self::Nothing,
             Value::List(_) => Self::List,
+            Value::Function(_) => Self::Function,
         }
     }
 }
@@ -64,42 +135,750 @@ impl std::fmt::Display for ValueKind {
             f,
             "{}",
             match self {
-                Self::Num => "num",
+                Self::Int => "int",
+                Self::Float => "float",
                 Self::String => "string",
                 Self::Bool => "bool",
                 Self::Nothing => "none",
                 Self::List => "list",
+                Self::Function => "function",
             }
         )
     }
 }
 
-fn eval_(expr: &Expression, env: &Environment, args: &Vec<Value>) -> Result<Value> {
-    match expr {
-        Expression::App(symbol, params) => {
-            let func = env.get_function(*symbol).unwrap();
+/// What a lazy builtin evaluates to: either a finished value, or an expression to evaluate in its
+/// place, so that a tail call in e.g. a branch of `if` doesn't grow the stack.
+pub enum Tail {
+    Value(Value),
+    Eval(Expression),
+}
+
+/// A call on the stack: the function it is running, `None` for lambdas, and where it was called
+/// from, `None` for calls made by the host.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub function: Option<Symbol>,
+    pub call_site: Option<Span>,
+}
+
+/// A call that is either finished or continues by evaluating a function body with new locals.
+enum Step {
+    Done(Value),
+    Eval(Expression, Vec<Value>, Frame),
+}
+
+/// Frames kept at either end of a call trace that is too long to show in full.
+const TRACE_EDGE: usize = 5;
+
+pub const DEFAULT_MAX_DEPTH: usize = 10_000;
+
+/// Stack left below which recursive passes over expressions grow the Rust stack, and by how much.
+pub(crate) const STACK_RED_ZONE: usize = 64 * 1024;
+pub(crate) const STACK_GROWTH: usize = 1024 * 1024;
+
+/// State of a running evaluation: the functions it can call and the stack of calls it is in.
+pub struct Context<'a> {
+    pub env: &'a Environment,
+    max_depth: usize,
+    stack: Vec<Frame>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(env: &'a Environment, max_depth: usize) -> Self {
+        Self {
+            env,
+            max_depth,
+            stack: vec![],
+        }
+    }
+
+    pub fn eval(&mut self, expr: &Expression, args: &[Value]) -> Result<Value> {
+        eval_(expr, self, args)
+    }
+
+    pub fn apply(&mut self, func: &Value, args: Vec<Value>) -> Result<Value> {
+        let step = apply_(func, args, None, self)?;
+        finish(step, self)
+    }
+
+    fn name(&self, frame: &Frame) -> &str {
+        frame
+            .function
+            .map_or("<lambda>", |symbol| self.env.get_name(symbol))
+    }
+
+    fn enter(&mut self, frame: Frame) -> Result<()> {
+        if self.stack.len() >= self.max_depth {
+            return Err(Error::coded(
+                Code::CallDepth,
+                format!(
+                    "maximum call depth of {} exceeded in {}",
+                    self.max_depth,
+                    self.name(&frame)
+                ),
+            ));
+        }
+
+        self.stack.push(frame);
+        Ok(())
+    }
+
+    /// The call stack, most recent call last, with runs of the same call collapsed and the middle
+    /// left out if it is still too long.
+    fn trace(&self) -> Vec<TraceFrame> {
+        let mut runs: Vec<(&Frame, usize)> = vec![];
+        for frame in &self.stack {
+            match runs.last_mut() {
+                Some((last, count)) if *last == frame => *count += 1,
+                _ => runs.push((frame, 1)),
+            }
+        }
+
+        let shown = |(frame, repeated): &(&Frame, usize)| TraceFrame::Call {
+            function: self.name(frame).to_string(),
+            call_site: frame.call_site.clone(),
+            repeated: *repeated,
+        };
+
+        if runs.len() <= TRACE_EDGE * 2 {
+            return runs.iter().map(shown).collect();
+        }
 
-            let eager_eval = || {
-                params
+        let hidden = &runs[TRACE_EDGE..runs.len() - TRACE_EDGE];
+        let omitted = hidden.iter().map(|(_, repeated)| repeated).sum();
+
+        let mut trace = runs[..TRACE_EDGE].iter().map(shown).collect::<Vec<_>>();
+        trace.push(TraceFrame::Omitted(omitted));
+        trace.extend(runs[runs.len() - TRACE_EDGE..].iter().map(shown));
+        trace
+    }
+}
+
+/// Evaluates arguments from left to right, which is an order that side effects can rely on.
+fn eval_params(params: &[Expression], ctx: &mut Context, args: &[Value]) -> Result<Vec<Value>> {
+    params.iter().map(|e| eval_(e, ctx, args)).collect()
+}
+
+fn eval_(expr: &Expression, ctx: &mut Context, args: &[Value]) -> Result<Value> {
+    run(expr.clone(), Cow::Borrowed(args), None, ctx)
+}
+
+/// Evaluates `expr`, which is the body of a call if `frame` is given, popping whatever frames it
+/// pushed once it is done and attaching the call trace to errors.
+fn run(
+    expr: Expression,
+    locals: Cow<[Value]>,
+    frame: Option<Frame>,
+    ctx: &mut Context,
+) -> Result<Value> {
+    let depth = ctx.stack.len();
+    // Grow the Rust stack on demand so that only `max_depth` limits how deep calls can nest
+    let result = stacker::maybe_grow(STACK_RED_ZONE, STACK_GROWTH, || {
+        run_(expr, locals, frame, ctx)
+    });
+
+    // The innermost call an error passes through still has the whole stack to trace
+    let result = result.map_err(|error| match error {
+        Error::Traced(..) => error,
+        _ if ctx.stack.is_empty() => error,
+        _ => Error::Traced(Box::new(error), ctx.trace()),
+    });
+    ctx.stack.truncate(depth);
+    result
+}
+
+/// Evaluates `expr` in a loop rather than recursing for calls in tail position, which are the
+/// body of an applied function or the expression a lazy builtin hands back. A tail call replaces
+/// the frame of the call it is made from instead of pushing a new one.
+fn run_(
+    mut expr: Expression,
+    mut locals: Cow<[Value]>,
+    mut frame: Option<Frame>,
+    ctx: &mut Context,
+) -> Result<Value> {
+    let mut in_call = false;
+    // Annotated return types of the calls this one stands in for through tail calls
+    let env = ctx.env;
+    let mut returns: Vec<(Symbol, &Annotation)> = vec![];
+
+    let value = loop {
+        if let Some(frame) = frame.take() {
+            if let Some(symbol) = frame.function {
+                let annotation = env.get_function(symbol).unwrap().annotation();
+                if let Some(annotation) = annotation.filter(|a| a.ret.is_some()) {
+                    if !returns.iter().any(|(s, _)| *s == symbol) {
+                        returns.push((symbol, annotation));
+                    }
+                }
+            }
+
+            match ctx.stack.last_mut() {
+                Some(last) if in_call => *last = frame,
+                _ => ctx.enter(frame)?,
+            }
+            in_call = true;
+        }
+
+        let (next, next_locals) = match &expr {
+            Expression::App(symbol, params, span) => {
+                let tail = match ctx.env.get_function(*symbol).unwrap().body() {
+                    FunctionBody::LazySystem(func) => {
+                        func(params, ctx, &locals).map_err(|e| e.at(span.clone()))?
+                    }
+                    FunctionBody::LazyNative(func) => {
+                        func(params, ctx, &locals).map_err(|e| e.at(span.clone()))?
+                    }
+                    _ => {
+                        let args = eval_params(params, ctx, &locals)?;
+                        match call(*symbol, args, Some(span.clone()), ctx)? {
+                            Step::Done(value) => Tail::Value(value),
+                            Step::Eval(body, args, next) => {
+                                frame = Some(next);
+                                expr = body;
+                                locals = Cow::Owned(args);
+                                continue;
+                            }
+                        }
+                    }
+                };
+
+                match tail {
+                    Tail::Value(value) => break value,
+                    Tail::Eval(next) => (next, None),
+                }
+            }
+            Expression::Call(callee, params, span) => {
+                let callee = eval_(callee, ctx, &locals)?;
+                let params = eval_params(params, ctx, &locals)?;
+
+                match apply_(&callee, params, Some(span.clone()), ctx)? {
+                    Step::Done(value) => break value,
+                    Step::Eval(body, args, next) => {
+                        frame = Some(next);
+                        (body, Some(args))
+                    }
+                }
+            }
+            Expression::Partial(symbol, params, _) => {
+                let params = params
                     .iter()
-                    .map(|e| eval_(e, env, args))
-                    .collect::<Result<Vec<_>>>()
-            };
+                    .map(|e| e.as_ref().map(|e| eval_(e, ctx, &locals)).transpose())
+                    .collect::<Result<Vec<_>>>()?;
+
+                break Value::Function(Rc::new(Callable::Partial(
+                    Rc::new(Callable::Named(*symbol)),
+                    params,
+                )));
+            }
+            Expression::Lambda(lambda, _) => {
+                break Value::Function(Rc::new(Callable::Closure(
+                    Rc::clone(lambda),
+                    locals.into_owned(),
+                )))
+            }
+            Expression::Ref(symbol, _) => break Value::Function(Rc::new(Callable::Named(*symbol))),
+            Expression::Arg(idx, _) => break locals[*idx].clone(),
+            Expression::Literal(value, _) => break value.clone(),
+            Expression::Do(exprs, _) => {
+                let (last, rest) = exprs.split_last().unwrap();
+                for expr in rest {
+                    eval_(expr, ctx, &locals)?;
+                }
+                (last.clone(), None)
+            }
+            Expression::Cond(branches, span) => {
+                let mut chosen = None;
+                for branch in branches.chunks(2) {
+                    let [test, result] = branch else {
+                        chosen = Some(branch[0].clone());
+                        break;
+                    };
+                    match eval_(test, ctx, &locals)? {
+                        Value::Bool(true) => {
+                            chosen = Some(result.clone());
+                            break;
+                        }
+                        Value::Bool(false) => {}
+                        other => {
+                            return Err(Error::coded(
+                                Code::WrongType,
+                                format!(
+                                    "a cond condition must be a bool, found {}",
+                                    ValueKind::from(&other)
+                                ),
+                            )
+                            .at(test.span()))
+                        }
+                    }
+                }
+
+                match chosen {
+                    Some(result) => (result, None),
+                    None => {
+                        return Err(
+                            Diagnostic::error("none of the conditions of cond were true")
+                                .with_code(Code::NoMatch)
+                                .with_label(span.clone(), "")
+                                .with_help("add a default result at the end")
+                                .into(),
+                        )
+                    }
+                }
+            }
+            Expression::Let(value, body, _) => {
+                let value = eval_(value, ctx, &locals)?;
+                let mut scope = std::mem::take(&mut locals).into_owned();
+                scope.push(value);
+                (body.as_ref().clone(), Some(scope))
+            }
+            Expression::Match(scrutinee, arms, span) => {
+                let value = eval_(scrutinee, ctx, &locals)?;
+                let mut chosen = None;
+                for arm in arms.iter() {
+                    let mut scope = locals.to_vec();
+                    if !matches(&arm.pattern, &value, &mut scope) {
+                        continue;
+                    }
+                    if let Some(guard) = &arm.guard {
+                        match eval_(guard, ctx, &scope)? {
+                            Value::Bool(true) => {}
+                            Value::Bool(false) => continue,
+                            other => {
+                                return Err(Error::coded(
+                                    Code::WrongType,
+                                    format!(
+                                        "a match guard must be a bool, found {}",
+                                        ValueKind::from(&other)
+                                    ),
+                                )
+                                .at(guard.span()))
+                            }
+                        }
+                    }
+                    chosen = Some((arm.body.clone(), scope));
+                    break;
+                }
 
-            match func.body() {
-                FunctionBody::Normal(expr) => eval_(&expr, env, &eager_eval()?),
-                FunctionBody::System(func) => func(&eager_eval()?),
-                FunctionBody::LazySystem(func) => func(params, eval_, env, args),
+                match chosen {
+                    Some((body, scope)) => (body, Some(scope)),
+                    None => {
+                        return Err(Diagnostic::error(format!(
+                            "no pattern matched {}",
+                            ValueKind::from(&value)
+                        ))
+                        .with_code(Code::NoMatch)
+                        .with_label(
+                            span.start..scrutinee.span().end,
+                            format!("{value} matched no arm"),
+                        )
+                        .with_help("add a `_` arm at the end to match anything else")
+                        .into())
+                    }
+                }
+            }
+            Expression::List(items, _) => {
+                break Value::List(
+                    items
+                        .iter()
+                        .map(|item| eval_(item, ctx, &locals))
+                        .collect::<Result<_>>()?,
+                )
             }
+            Expression::Temp => {
+                return Err(Error::General(
+                    "attemped to evaluate temp expr: this is a BUG".into(),
+                ))
+            }
+        };
+
+        expr = next;
+        if let Some(args) = next_locals {
+            locals = Cow::Owned(args);
+        }
+    };
+
+    for (symbol, annotation) in returns {
+        let ret = annotation.ret.as_ref().unwrap();
+        if !ret.admits(&value) {
+            let message = format!(
+                "{} is annotated to return {ret}, but returned {}",
+                env.get_name(symbol),
+                mismatch(ret, &value)
+            );
+            return Err(Diagnostic::error(message)
+                .with_code(Code::WrongType)
+                .with_label(
+                    annotation.span.clone(),
+                    format!("annotated as {annotation}"),
+                )
+                .into());
         }
-        Expression::Arg(idx) => Ok(args[*idx].clone()),
-        Expression::Literal(value) => Ok(value.clone()),
-        Expression::Temp => Err(Error::General(
-            "attemped to evaluate temp expr: this is a BUG".into(),
-        )),
     }
+    Ok(value)
 }
 
 pub fn eval(expr: &Expression, env: &Environment) -> Result<Value> {
-    eval_(expr, env, &vec![])
+    Context::new(env, DEFAULT_MAX_DEPTH).eval(expr, &[])
+}
+
+fn finish(step: Step, ctx: &mut Context) -> Result<Value> {
+    match step {
+        Step::Done(value) => Ok(value),
+        Step::Eval(body, args, frame) => run(body, Cow::Owned(args), Some(frame), ctx),
+    }
+}
+
+fn locate(error: Error, call_site: &Option<Span>) -> Error {
+    match call_site {
+        Some(span) => error.at(span.clone()),
+        None => error,
+    }
+}
+
+/// Whether `value` matches `pattern`, pushing the values of the names it binds onto `scope`.
+fn matches(pattern: &Pattern, value: &Value, scope: &mut Vec<Value>) -> bool {
+    match (pattern, value) {
+        (Pattern::Wildcard(_), _) => true,
+        (Pattern::Bind(_), value) => {
+            scope.push(value.clone());
+            true
+        }
+        (Pattern::Literal(Value::Int(a), _), Value::Int(b)) => a == b,
+        (Pattern::Literal(literal, _), value) => match (literal.as_float(), value.as_float()) {
+            (Some(a), Some(b)) => a == b,
+            _ => literal == value,
+        },
+        (Pattern::List(items, rest, _), Value::List(list)) => {
+            let fits = match rest {
+                Some(_) => list.len() >= items.len(),
+                None => list.len() == items.len(),
+            };
+            fits && items
+                .iter()
+                .zip(list)
+                .all(|(item, value)| matches(item, value, scope))
+                && rest
+                    .as_ref()
+                    .is_none_or(|rest| matches(rest, &Value::List(list.skip(items.len())), scope))
+        }
+        (Pattern::List(..), _) => false,
+    }
+}
+
+/// What kind of value `value` is, pointing out the offending element of a list that `ty` doesn't
+/// admit.
+fn mismatch(ty: &Type, value: &Value) -> String {
+    match (ty, value) {
+        (Type::List(element), Value::List(items)) => {
+            match items.iter().find(|item| !element.admits(item)) {
+                Some(item) => format!("list containing {}", mismatch(element, item)),
+                None => ValueKind::from(value).to_string(),
+            }
+        }
+        _ => ValueKind::from(value).to_string(),
+    }
+}
+
+/// Checks arguments against the types annotated on the parameters of the function `name`.
+fn check_args(name: &str, annotation: &Annotation, args: &[Value]) -> Result<()> {
+    for (i, (param, arg)) in annotation.params.iter().zip(args).enumerate() {
+        match param {
+            Some(ty) if !ty.admits(arg) => {
+                return Err(Error::coded(
+                    Code::WrongType,
+                    format!(
+                        "argument {} of {name} is annotated as {ty}, found {}",
+                        i + 1,
+                        mismatch(ty, arg)
+                    ),
+                ))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Calls a declared function with already evaluated arguments.
+fn call(
+    symbol: Symbol,
+    args: Vec<Value>,
+    call_site: Option<Span>,
+    ctx: &mut Context,
+) -> Result<Step> {
+    // Builtins don't get a frame of their own, so their errors point at the call instead
+    let located = |error| locate(error, &call_site);
+    let literal = |value| Expression::Literal(value, call_site.clone().unwrap_or_default());
+
+    let func = ctx.env.get_function(symbol).unwrap();
+    // Calls and function values are made knowing how many arguments the function took then, which
+    // a later load may have changed
+    if args.len() != func.args() {
+        let name = ctx.env.get_name(symbol);
+        return Err(located(
+            Diagnostic::error(format!(
+                "{name} takes {} arguments but was called with {}",
+                func.args(),
+                args.len()
+            ))
+            .with_code(Code::ArityChanged)
+            .with_note(format!(
+                "{name} was loaded again with a different number of arguments after this call was made"
+            ))
+            .into(),
+        ));
+    }
+
+    let tail = match func.body() {
+        FunctionBody::Normal(expr) => {
+            if let Some(annotation) = func.annotation() {
+                check_args(ctx.env.get_name(symbol), annotation, &args).map_err(located)?;
+            }
+            let frame = Frame {
+                function: Some(symbol),
+                call_site,
+            };
+            return Ok(Step::Eval(expr.clone(), args, frame));
+        }
+        FunctionBody::System(func) => return func(&args).map(Step::Done).map_err(located),
+        FunctionBody::Native(func) => return func(&args).map(Step::Done).map_err(located),
+        FunctionBody::LazySystem(func) => {
+            let params = args.into_iter().map(literal).collect::<Vec<_>>();
+            func(&params, ctx, &[]).map_err(located)?
+        }
+        FunctionBody::LazyNative(func) => {
+            let params = args.into_iter().map(literal).collect::<Vec<_>>();
+            func(&params, ctx, &[]).map_err(located)?
+        }
+    };
+
+    let frame = Frame {
+        function: Some(symbol),
+        call_site,
+    };
+
+    Ok(match tail {
+        Tail::Value(value) => Step::Done(value),
+        Tail::Eval(expr) => Step::Eval(expr, vec![], frame),
+    })
+}
+
+/// Applies a function value to already evaluated arguments. Too few arguments make a partial
+/// application, while extra arguments are passed on to the function it returns.
+pub fn apply(func: &Value, args: Vec<Value>, env: &Environment) -> Result<Value> {
+    Context::new(env, DEFAULT_MAX_DEPTH).apply(func, args)
+}
+
+fn apply_(
+    func: &Value,
+    mut args: Vec<Value>,
+    call_site: Option<Span>,
+    ctx: &mut Context,
+) -> Result<Step> {
+    let located = |error| locate(error, &call_site);
+
+    let Value::Function(callable) = func else {
+        return Err(located(Error::coded(
+            Code::NotAFunction,
+            format!("cannot apply {} as a function", ValueKind::from(func)),
+        )));
+    };
+
+    let arity = callable.arity(ctx.env);
+    if args.len() < arity {
+        let partial = match &**callable {
+            Callable::Partial(inner, bound) => {
+                Callable::Partial(Rc::clone(inner), fill(bound, args))
+            }
+            _ => {
+                let missing = arity - args.len();
+                let bound = args.into_iter().map(Some);
+                Callable::Partial(
+                    Rc::clone(callable),
+                    bound.chain((0..missing).map(|_| None)).collect(),
+                )
+            }
+        };
+        return Ok(Step::Done(Value::Function(Rc::new(partial))));
+    }
+
+    let supplied = args.len();
+    let rest = args.split_off(arity);
+    if rest.is_empty() {
+        return invoke(callable, args, call_site, ctx);
+    }
+
+    let step = invoke(callable, args, call_site.clone(), ctx)?;
+    match finish(step, ctx)? {
+        result @ Value::Function(_) => apply_(&result, rest, call_site, ctx),
+        _ => Err(located(Error::coded(
+            Code::TooManyArguments,
+            format!("function takes {arity} arguments but {supplied} were supplied"),
+        ))),
+    }
+}
+
+/// Calls a function value with exactly as many arguments as it takes.
+fn invoke(
+    callable: &Callable,
+    args: Vec<Value>,
+    call_site: Option<Span>,
+    ctx: &mut Context,
+) -> Result<Step> {
+    match callable {
+        Callable::Named(symbol) => call(*symbol, args, call_site, ctx),
+        Callable::Closure(lambda, captured) => {
+            let mut locals = captured.clone();
+            locals.extend(args);
+            let frame = Frame {
+                function: None,
+                call_site,
+            };
+            Ok(Step::Eval(lambda.body.clone(), locals, frame))
+        }
+        Callable::Partial(inner, bound) => {
+            let args = fill(bound, args).into_iter().flatten().collect();
+            invoke(inner, args, call_site, ctx)
+        }
+    }
+}
+
+/// An embeddable `f` interpreter: owns an [`Environment`] that source files are loaded into and
+/// that functions can be called from by name.
+pub struct Interpreter {
+    env: Environment,
+    max_depth: usize,
+    type_check: bool,
+    /// Everything loaded or evaluated, which the spans of errors and warnings point into.
+    sources: SourceMap,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    /// Creates an interpreter with the builtin functions from [`default_env`] loaded.
+    pub fn new() -> Self {
+        Self::with_env(default_env())
+    }
+
+    pub fn with_env(env: Environment) -> Self {
+        Self {
+            env,
+            max_depth: DEFAULT_MAX_DEPTH,
+            type_check: false,
+            sources: SourceMap::default(),
+        }
+    }
+
+    pub fn env(&self) -> &Environment {
+        &self.env
+    }
+
+    pub fn env_mut(&mut self) -> &mut Environment {
+        &mut self.env
+    }
+
+    /// Sets how many calls deep evaluation may go before failing with an error, instead of
+    /// overflowing the Rust stack. Tail calls don't count towards this.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    /// Turns on checking the types of loaded files and evaluated expressions before running them.
+    pub fn set_type_check(&mut self, type_check: bool) {
+        self.type_check = type_check;
+    }
+
+    /// The sources loaded or evaluated so far, to render errors and warnings against with
+    /// [`Error::log_in`] and [`Diagnostic::render_in`].
+    pub fn sources(&self) -> &SourceMap {
+        &self.sources
+    }
+
+    /// Tokenizes `src` after the sources read before it, so its spans don't point into them.
+    fn tokenize<'s>(
+        &mut self,
+        src: &'s str,
+        tokenize: fn(&'s str) -> Result<Vec<Token<'s>>>,
+    ) -> Result<(Vec<Token<'s>>, usize)> {
+        let base = self.sources.add(src);
+        let tokens = tokenize(src).map_err(|e| e.offset(base))?;
+        Ok((tokens.into_iter().map(|t| t.offset(base)).collect(), base))
+    }
+
+    fn context(&self) -> Context<'_> {
+        Context::new(&self.env, self.max_depth)
+    }
+
+    /// Parses every declaration in `src` into the environment. If anything in it fails to parse or
+    /// type check, the environment is left as it was.
+    pub fn load(&mut self, src: &str) -> Result<()> {
+        let (tokens, _) = self.tokenize(src, tokenize)?;
+        self.env.begin();
+        let loaded = parse_file(&tokens, &mut self.env).and_then(|()| match self.type_check {
+            true => check(&self.env).map(|_| ()),
+            false => Ok(()),
+        });
+        match loaded {
+            Ok(()) => self.env.commit(),
+            Err(_) => self.env.rollback(),
+        }
+        loaded
+    }
+
+    /// Parses and evaluates a single expression, like a line typed into the repl.
+    pub fn eval(&mut self, src: &str) -> Result<Value> {
+        let (tokens, base) = self.tokenize(src, tokenize_expr)?;
+        let end = base + src.len();
+        let expr =
+            parse_expr(&mut tokens.iter(), &vec![], &self.env).map_err(|e| e.at(end..end))?;
+        if self.type_check {
+            check_expr(&expr, &self.env)?;
+        }
+        self.context().eval(&expr, &[])
+    }
+
+    /// The type of the function `name`, inferred even if other functions don't type check.
+    pub fn type_of(&self, name: &str) -> Result<Scheme> {
+        let symbol = self.env.get_symbol(name);
+        symbol
+            .and_then(|symbol| infer(&self.env).types.remove(&symbol))
+            .ok_or_else(|| {
+                Error::coded(
+                    Code::UnknownFunction,
+                    format!("cannot find function {name}"),
+                )
+            })
+    }
+
+    /// Warnings about the loaded functions found while inferring their types, such as matches
+    /// that don't cover every value.
+    pub fn warnings(&self) -> Vec<Diagnostic> {
+        let mut warnings = infer(&self.env).warnings;
+        warnings.sort_by_key(|warning| warning.primary_span().map(|span| span.start));
+        warnings
+    }
+
+    /// Calls the function `name` with already evaluated arguments, with the same partial
+    /// application rules as [`Interpreter::apply`].
+    pub fn call(&self, name: &str, args: Vec<Value>) -> Result<Value> {
+        let (symbol, _) = self.env.get_entry(name).ok_or_else(|| {
+            Error::coded(
+                Code::UnknownFunction,
+                format!("cannot find function {name}"),
+            )
+        })?;
+
+        self.apply(&Value::Function(Rc::new(Callable::Named(symbol))), args)
+    }
+
+    /// Applies a function value, e.g. one returned by [`Interpreter::call`], to arguments.
+    pub fn apply(&self, func: &Value, args: Vec<Value>) -> Result<Value> {
+        self.context().apply(func, args)
+    }
 }
@@ -0,0 +1,19 @@
+pub mod checker;
+pub mod diagnostic;
+pub mod env;
+pub mod error;
+pub mod interpreter;
+pub mod native;
+pub mod parser;
+pub mod span;
+pub mod tokenizer;
+pub mod types;
+
+pub use checker::{check, check_expr};
+pub use diagnostic::{Code, Diagnostic, Severity};
+pub use env::{default_env, Environment};
+pub use error::{Error, Result};
+pub use interpreter::{eval, Interpreter, List, Value};
+pub use parser::{parse_expr, parse_file};
+pub use tokenizer::{tokenize, tokenize_expr};
+pub use types::{Scheme, Type};
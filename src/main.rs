@@ -1,64 +1,58 @@
-mod env;
-mod error;
-mod interpreter;
-mod parser;
-mod span;
-mod tokenizer;
-
-use env::FunctionBody;
-use error::{Error, UnwrapPretty};
+use f::{
+    error::{Error, UnwrapPretty},
+    Code, Interpreter,
+};
 use rustyline::Editor;
 use std::{env::args, path::Path};
 
-use parser::{parse_expr, parse_file};
-use tokenizer::tokenize;
-
-use crate::{env::Environment, error::Result, interpreter::Value};
-
 fn load_file<P: AsRef<Path>>(
     path: P,
-    env: &mut Environment,
-) -> core::result::Result<(), (Error, String)> {
+    interpreter: &mut Interpreter,
+    type_check: bool,
+) -> f::Result<()> {
     let file = std::fs::read_to_string(&path).map_err(|_| {
-        (
-            Error::General(format!(
-                "could not load file {}",
-                path.as_ref().to_str().unwrap()
-            )),
-            "".to_string(),
-        )
+        Error::General(format!(
+            "could not load file {}",
+            path.as_ref().to_str().unwrap()
+        ))
     })?;
 
-    let tokens = tokenize(&file).map_err(|e| (e, file.clone()))?;
-    parse_file(&tokens, env).map_err(|e| (e, file))?;
+    interpreter.load(&file)?;
+    if type_check {
+        for warning in interpreter.warnings() {
+            warning.render_in(interpreter.sources());
+            println!();
+        }
+    }
     Ok(())
 }
 
-pub fn repl() -> rustyline::Result<()> {
-    let mut env = env::default_env();
+pub fn repl(type_check: bool) -> rustyline::Result<()> {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_type_check(type_check);
     let mut editor = Editor::<()>::new()?;
 
-    println!("repl: {} functions loaded", env.size());
-
-    fn run(line: &str, env: &mut Environment) -> Result<Value> {
-        let tokens = tokenize(line)?;
-        let ast = parse_expr(&mut tokens.iter(), &vec![], env)?;
-        interpreter::eval(&ast, env)
-    }
+    println!("repl: {} functions loaded", interpreter.env().size());
 
     while let Ok(line) = editor.readline(">> ") {
         editor.add_history_entry(line.clone());
         if line.trim() == ":exit" {
             std::process::exit(1);
         }
-        if line.starts_with(":load ") {
+        if let Some(name) = line.strip_prefix(":type ") {
+            match interpreter.type_of(name.trim()) {
+                Ok(scheme) => println!("{} : {}", name.trim(), scheme),
+                Err(err) => err.log_in(interpreter.sources()),
+            }
+        } else if line.starts_with(":load ") {
             let (_, path) = line.split_once(":load ").unwrap();
-            load_file(path, &mut env).unwrap_or_else(|(err, file)| err.log(&file))
+            if let Err(err) = load_file(path, &mut interpreter, type_check) {
+                err.log_in(interpreter.sources());
+            }
         } else {
-            let run = run(&line, &mut env);
-            match run {
+            match interpreter.eval(&line) {
                 Ok(run) => println!("{}", run),
-                Err(err) => err.log(&line),
+                Err(err) => err.log_in(interpreter.sources()),
             }
         }
     }
@@ -67,28 +61,27 @@ pub fn repl() -> rustyline::Result<()> {
 }
 
 fn main() -> core::result::Result<(), Box<dyn std::error::Error>> {
-    let path = args().nth(1).unwrap_or_default();
+    // `--check` checks types before running anything
+    let (flags, paths): (Vec<_>, Vec<_>) = args().skip(1).partition(|arg| arg.starts_with("--"));
+    let type_check = flags.iter().any(|flag| flag == "--check");
+    let path = paths.into_iter().next().unwrap_or_default();
 
     if path.is_empty() {
-        repl()?;
+        repl(type_check)?;
     } else {
-        let mut env = env::default_env();
-
-        load_file(&path, &mut env).unwrap_or_else(|(err, file)| {
-            err.log(&file);
-            std::process::exit(1);
-        });
+        let mut interpreter = Interpreter::new();
+        interpreter.set_type_check(type_check);
 
-        let main = env.get_function("main").unwrap_or_else(|| {
-            Err(Error::General("no main function found in file".into())).unwrap_pretty(&path)
-        });
+        load_file(&path, &mut interpreter, type_check).unwrap_pretty(interpreter.sources());
 
-        match main.body() {
-            FunctionBody::Normal(expr) => {
-                interpreter::eval(&expr, &env).unwrap_pretty(&path);
-            }
-            FunctionBody::System(_) | FunctionBody::LazySystem(_) => unreachable!(),
+        if interpreter.env().get_function("main").is_none() {
+            Err(Error::coded(Code::NoMain, "no main function found in file"))
+                .unwrap_pretty(interpreter.sources())
         }
+
+        interpreter
+            .call("main", vec![])
+            .unwrap_pretty(interpreter.sources());
     }
 
     Ok(())
@@ -0,0 +1,175 @@
+use crate::{
+    diagnostic::Code,
+    env::NativeFunction,
+    error::{Error, Result},
+    interpreter::{List, Value, ValueKind},
+};
+use std::rc::Rc;
+
+/// Conversion from an `f` value into a Rust argument of a native function.
+pub trait FromValue: Sized {
+    /// Name of the expected kind, used in argument type errors.
+    const KIND: &'static str;
+
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+/// Conversion from the return value of a native function into an `f` value.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+impl FromValue for Value {
+    const KIND: &'static str = "any";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl FromValue for f64 {
+    const KIND: &'static str = "float";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        value.as_float()
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Nothing
+    }
+}
+
+macro_rules! impl_value_conversions {
+    ($(($t:ty, $variant:ident, $kind:literal)),+) => {
+        $(
+            impl FromValue for $t {
+                const KIND: &'static str = $kind;
+
+                fn from_value(value: &Value) -> Option<Self> {
+                    match value {
+                        Value::$variant(x) => Some(x.clone()),
+                        _ => None,
+                    }
+                }
+            }
+
+            impl IntoValue for $t {
+                fn into_value(self) -> Value {
+                    Value::$variant(self)
+                }
+            }
+        )+
+    };
+}
+
+impl_value_conversions![
+    (i64, Int, "int"),
+    (Rc<str>, String, "string"),
+    (bool, Bool, "bool"),
+    (List, List, "list")
+];
+
+impl FromValue for String {
+    const KIND: &'static str = "string";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(s) => Some(s.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(self.into())
+    }
+}
+
+impl FromValue for Vec<Value> {
+    const KIND: &'static str = "list";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::List(list) => Some(list.iter().cloned().collect()),
+            _ => None,
+        }
+    }
+}
+
+impl IntoValue for Vec<Value> {
+    fn into_value(self) -> Value {
+        Value::List(self.into())
+    }
+}
+
+fn from_arg<T: FromValue>(args: &[Value], index: usize) -> Result<T> {
+    T::from_value(&args[index]).ok_or_else(|| {
+        Error::coded(
+            Code::WrongType,
+            format!(
+                "wrong argument type for index {}: expected {}, found {}",
+                index + 1,
+                T::KIND,
+                ValueKind::from(&args[index])
+            ),
+        )
+    })
+}
+
+/// Rust closures that can be registered with [`Environment::register`] as native functions.
+///
+/// `Args` is the tuple of argument types, which only exists to keep the implementations for
+/// closures of different arities apart.
+///
+/// [`Environment::register`]: crate::env::Environment::register
+pub trait IntoNative<Args> {
+    const ARGS: usize;
+
+    fn into_native(self) -> NativeFunction;
+}
+
+macro_rules! impl_into_native {
+    ($(($num_args:literal, $($arg:ident $idx:literal),*)),+) => {
+        $(
+            impl<F, R, $($arg),*> IntoNative<($($arg,)*)> for F
+            where
+                F: Fn($($arg),*) -> Result<R> + 'static,
+                R: IntoValue,
+                $($arg: FromValue,)*
+            {
+                const ARGS: usize = $num_args;
+
+                #[allow(unused_variables)]
+                fn into_native(self) -> NativeFunction {
+                    Box::new(move |args| {
+                        self($(from_arg::<$arg>(args, $idx)?),*).map(IntoValue::into_value)
+                    })
+                }
+            }
+        )+
+    };
+}
+
+impl_into_native![
+    (0,),
+    (1, A 0),
+    (2, A 0, B 1),
+    (3, A 0, B 1, C 2),
+    (4, A 0, B 1, C 2, D 3),
+    (5, A 0, B 1, C 2, D 3, E 4)
+];
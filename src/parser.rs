@@ -1,87 +1,1221 @@
+use std::{iter::Peekable, rc::Rc};
+
 use crate::{
-    env::{Environment, Function, Symbol},
+    checker::calls_in,
+    diagnostic::{Code, Diagnostic},
+    env::{Associativity, Environment, Function, FunctionBody, Operator, Symbol},
     error::{Error, Result},
-    interpreter::Value,
+    interpreter::{Value, STACK_GROWTH, STACK_RED_ZONE},
+    span::Span,
     tokenizer::{Token, TokenKind},
+    types::{Annotation, Type, TypeParser},
 };
 
-#[derive(Debug)]
+/// Children are reference counted so that the evaluator can cheaply hold on to the expression it
+/// continues with after a tail call. Every expression records the span of source it was parsed
+/// from, covering its arguments too.
+#[derive(Debug, Clone)]
 pub enum Expression {
-    App(Symbol, Vec<Expression>), // Maybe use arena allocator for better cache locality
-    Arg(usize),
-    Literal(Value),
+    App(Symbol, Rc<[Expression]>, Span), // Maybe use arena allocator for better cache locality
+    Call(Rc<Expression>, Rc<[Expression]>, Span),
+    Partial(Symbol, Rc<[Option<Expression>]>, Span),
+    Lambda(Rc<Lambda>, Span),
+    Ref(Symbol, Span),
+    Arg(usize, Span),
+    Literal(Value, Span),
+    /// A list literal, whose elements are evaluated when it is.
+    List(Rc<[Expression]>, Span),
+    /// `do [...]`, which evaluates its expressions in order and results in the last.
+    Do(Rc<[Expression]>, Span),
+    /// `cond [condition result ... default]`, which only evaluates the result of the first true
+    /// condition, or the default if there is one and none are.
+    Cond(Rc<[Expression]>, Span),
+    /// `let name value body`, where the body sees the value as a new local after all the others.
+    Let(Rc<Expression>, Rc<Expression>, Span),
+    /// `match value [pattern -> result ...]`, which only evaluates the result of the first arm
+    /// whose pattern matches.
+    Match(Rc<Expression>, Rc<[Arm]>, Span),
     Temp,
 }
 
-pub fn parse_file<'a>(tokens: &[Token], env: &mut Environment) -> Result<()> {
+impl Expression {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::App(_, _, span)
+            | Self::Call(_, _, span)
+            | Self::Partial(_, _, span)
+            | Self::Lambda(_, span)
+            | Self::Ref(_, span)
+            | Self::Arg(_, span)
+            | Self::Literal(_, span)
+            | Self::List(_, span)
+            | Self::Do(_, span)
+            | Self::Cond(_, span)
+            | Self::Let(_, _, span)
+            | Self::Match(_, _, span) => span.clone(),
+            Self::Temp => Span::default(),
+        }
+    }
+}
+
+/// An arm of a `match`. The guard and result see the names the pattern binds as new locals, in the
+/// order they are written.
+#[derive(Debug)]
+pub struct Arm {
+    pub pattern: Pattern,
+    pub guard: Option<Expression>,
+    pub body: Expression,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// `_`, which matches anything.
+    Wildcard(Span),
+    /// A name, which matches anything and binds it.
+    Bind(Span),
+    /// A number, string, `true`, `false` or `none`, matching values equal to it.
+    Literal(Value, Span),
+    /// `[a b]` matches lists of exactly the elements given, while `[a b : rest]` matches lists
+    /// starting with them and matches the rest of the list against `rest`.
+    List(Vec<Pattern>, Option<Box<Pattern>>, Span),
+}
+
+impl Pattern {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Wildcard(span)
+            | Self::Bind(span)
+            | Self::Literal(_, span)
+            | Self::List(_, _, span) => span.clone(),
+        }
+    }
+
+    /// Whether the pattern matches every value of the type it is matched against.
+    pub fn is_irrefutable(&self) -> bool {
+        matches!(self, Self::Wildcard(_) | Self::Bind(_))
+    }
+}
+
+/// An anonymous function. Its body sees the locals of the enclosing scope followed by its own
+/// `args`, which is exactly the layout of the values it is called with once captured.
+#[derive(Debug)]
+pub struct Lambda {
+    pub args: usize,
+    pub body: Expression,
+}
+
+pub fn parse_file(tokens: &[Token], env: &mut Environment) -> Result<()> {
+    // Declare every function up front so that bodies can call functions declared further down,
+    // which is what makes mutual recursion possible
+    let mut declared = vec![];
+    for (i, token) in tokens.iter().enumerate() {
+        if let Token::Decl(name, span) = token {
+            // Operator declarations also hold for the whole file, and are checked properly below
+            if let Some(associativity) = associativity(name) {
+                let mut rest = tokens[i + 1..].iter().peekable();
+                if let Ok((name, _, operator)) = parse_fixity(associativity, span, &mut rest) {
+                    env.set_operator(name, operator);
+                }
+                continue;
+            }
+
+            let header = tokens[i + 1..]
+                .iter()
+                .map_while(type_word)
+                .collect::<Vec<_>>();
+            let args = match parse_params(&mut TypeParser::new(&header)) {
+                Ok((args, _)) | Err((args, _)) => args.len(),
+            };
+            declared.push((*name, span, args));
+        }
+    }
+
+    check_arities(&declared, env)?;
+    for (name, _, args) in declared {
+        if env.get_function(name).map(Function::args) != Some(args) {
+            env.insert_function(name, Function::new(args, Expression::Temp));
+        }
+    }
+
+    let end = tokens.last().map_or(0, |token| token.span().end);
     let mut tokens = tokens.iter().peekable();
+    let mut errors = vec![];
 
     while let Some(token) = tokens.next() {
-        let Token::Decl(name, _) = token else {
-            Err(Error::Spanned(
-                format!("expected declaration found {}", token.kind()),
-                token.span(),
-            ))?
+        if let Err(error) = parse_decl(token, &mut tokens, env) {
+            // Errors running into the end of the file point just past the last token
+            errors.push(error.at(end..end));
+            // Declarations start at the beginning of a line, so the next one is a safe place to
+            // pick up from
+            while tokens.next_if(|t| t.kind() != TokenKind::Decl).is_some() {}
+        }
+    }
+
+    match Error::many(errors) {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+/// Checks that declarations replacing functions that are already loaded keep their number of
+/// arguments, unless everything calling them is declared again too. Calls are parsed knowing how
+/// many arguments they take, so the old callers would run with the wrong number of them.
+fn check_arities(declared: &[(&str, &Span, usize)], env: &Environment) -> Result<()> {
+    let redeclared = |symbol| {
+        declared
+            .iter()
+            .any(|(name, ..)| env.get_symbol(name) == Some(symbol))
+    };
+
+    for &(name, span, args) in declared {
+        let Some((symbol, old)) = env.get_entry(name) else {
+            continue;
         };
+        if old.args() == args {
+            continue;
+        }
 
-        let mut args = vec![];
-        while let Some(Token::Name(name, _)) = tokens.next_if(|t| t.kind() == TokenKind::Name) {
-            args.push(*name);
+        let callers = env
+            .functions()
+            .filter(|(caller, _)| !redeclared(*caller))
+            .filter(|(_, func)| match func.body() {
+                FunctionBody::Normal(body) => {
+                    let mut calls = vec![];
+                    calls_in(body, &mut calls);
+                    calls.contains(&symbol)
+                }
+                _ => false,
+            })
+            .map(|(caller, _)| env.get_name(caller))
+            .collect::<Vec<_>>();
+        if let Some(caller) = callers.iter().min() {
+            Err(Diagnostic::error(format!(
+                "{name} can't go from {} to {} while {caller} uses it",
+                arguments(old.args()),
+                arguments(args)
+            ))
+            .with_code(Code::ArityChanged)
+            .with_label(
+                span.start - 1..span.end,
+                format!("{name} now takes {}", arguments(args)),
+            )
+            .with_note(format!(
+                "{caller} was loaded before and expects {name} to take {}",
+                arguments(old.args())
+            ))
+            .with_help(format!(
+                "declare {caller} again along with {name}, or give the new {name} another name"
+            )))?
         }
+    }
+    Ok(())
+}
 
-        let next = tokens.next();
-        // TODO: Rewrite this
-        if next.is_none() {
-            return Err(Error::General("expected arrow, found <eof>".into()));
-        } else if next.unwrap().kind() != TokenKind::Arrow {
-            return Err(Error::Spanned(
-                format!("expected arrow, found {}", next.unwrap().kind()),
-                next.unwrap().span(),
-            ));
-        } else {
-            // TODO: make this better
-            env.insert_function(name, Function::new(args.len(), Expression::Temp));
-            let expr = parse_expr(&mut tokens, &args, env)?;
-            env.insert_function(name, Function::new(args.len(), expr));
+/// The associativity an operator declaration like `\infixl 6 +` gives, if `name` is one.
+fn associativity(name: &str) -> Option<Associativity> {
+    match name {
+        "infixl" => Some(Associativity::Left),
+        "infixr" => Some(Associativity::Right),
+        "infix" => Some(Associativity::None),
+        _ => None,
+    }
+}
+
+/// Parses the precedence and name of an operator declaration.
+fn parse_fixity<'a>(
+    associativity: Associativity,
+    span: &Span,
+    tokens: &mut Peekable<std::slice::Iter<'a, Token<'a>>>,
+) -> Result<(&'a str, Span, Operator)> {
+    let declaration = span.start - 1..span.end;
+    let precedence = match tokens.next_if(|t| t.kind() != TokenKind::Decl) {
+        Some(Token::Int(n, span)) => u8::try_from(*n).map_err(|_| {
+            Error::from(
+                Diagnostic::error("operator precedence out of range")
+                    .with_code(Code::InvalidOperator)
+                    .with_label(span.clone(), "expected a precedence from 0 to 255"),
+            )
+        })?,
+        Some(token) => Err(Diagnostic::error(format!(
+            "expected precedence, found {}",
+            token.kind()
+        ))
+        .with_code(Code::UnexpectedToken)
+        .with_label(token.span(), "expected a number")
+        .with_secondary(declaration.clone(), "in this operator declaration"))?,
+        None => Err(eof("precedence").at(declaration.clone()))?,
+    };
+    let (name, name_span) = match tokens.next_if(|t| t.kind() != TokenKind::Decl) {
+        Some(Token::Name(name, span)) => (*name, span.clone()),
+        Some(token) => Err(Diagnostic::error(format!(
+            "expected operator name, found {}",
+            token.kind()
+        ))
+        .with_code(Code::UnexpectedToken)
+        .with_label(token.span(), "expected a name")
+        .with_secondary(declaration, "in this operator declaration"))?,
+        None => Err(eof("operator name").at(declaration))?,
+    };
+
+    Ok((
+        name,
+        name_span,
+        Operator {
+            precedence,
+            associativity,
+        },
+    ))
+}
+
+fn parse_decl<'a>(
+    token: &'a Token<'a>,
+    tokens: &mut Peekable<std::slice::Iter<'a, Token<'a>>>,
+    env: &mut Environment,
+) -> Result<()> {
+    let Token::Decl(name, span) = token else {
+        Err(
+            Diagnostic::error(format!("expected declaration, found {}", token.kind()))
+                .with_code(Code::UnexpectedToken)
+                .with_label(token.span(), "expected declaration")
+                .with_help("declarations start with a `\\` at the beginning of a line"),
+        )?
+    };
+
+    if let Some(associativity) = associativity(name) {
+        let (operator, operator_span, _) = parse_fixity(associativity, span, tokens)?;
+        return match env.get_entry(operator) {
+            Some((_, func)) if func.args() == 2 => Ok(()),
+            entry => Err(Diagnostic::error(format!(
+                "{operator} can't be an operator since it doesn't take two arguments"
+            ))
+            .with_code(Code::InvalidOperator)
+            .with_label(
+                operator_span,
+                match entry {
+                    Some((_, func)) => format!("{operator} takes {}", arguments(func.args())),
+                    None => format!("{operator} is not a function"),
+                },
+            )
+            .into()),
+        };
+    }
+
+    // The parameters, their types and the return type are parsed from the words up to the body, and
+    // only then consumed, so that a missing arrow doesn't swallow the declaration after it
+    let header = tokens.clone().map_while(type_word).collect::<Vec<_>>();
+    let mut types = TypeParser::new(&header);
+    let invalid = |message: String, pos: usize| match tokens.clone().nth(pos) {
+        Some(token) => Diagnostic::error(format!("invalid type annotation: {message}"))
+            .with_code(Code::InvalidAnnotation)
+            .with_label(token.span(), "")
+            .into(),
+        None => eof("type"),
+    };
+
+    let (args, params) = parse_params(&mut types).map_err(|(_, m)| invalid(m, types.pos()))?;
+    if !types.eat("->") {
+        let Some(next) = tokens.clone().nth(types.pos()) else {
+            return Err(eof("arrow"));
+        };
+        return Err(
+            Diagnostic::error(format!("expected arrow, found {}", next.kind()))
+                .with_code(Code::UnexpectedToken)
+                .with_label(next.span(), "expected `->`")
+                .with_secondary(
+                    span.start - 1..span.end,
+                    format!("in the declaration of {name}"),
+                )
+                .into(),
+        );
+    }
+
+    // A return type is a type followed by another arrow, which a body never is
+    let before_ret = types.pos();
+    let ret = match types.atom() {
+        Ok(ty) if types.eat("->") => Some(ty),
+        _ => {
+            types.rewind(before_ret);
+            None
         }
+    };
+
+    let consumed = types.pos();
+    let end = tokens
+        .clone()
+        .nth(consumed - 1)
+        .map_or(span.end, |t| t.span().end);
+    tokens.nth(consumed - 1);
+
+    let annotation = (params.iter().any(Option::is_some) || ret.is_some()).then(|| Annotation {
+        params,
+        ret,
+        span: span.start - 1..end,
+    });
+
+    // The body stops at the next declaration, so that a body missing arguments doesn't swallow it
+    let mut consumed = vec![];
+    let mut body = std::iter::from_fn(|| {
+        let token = tokens.next_if(|t| t.kind() != TokenKind::Decl);
+        consumed.extend(token);
+        token
+    });
+    let expr = parse_expr(&mut body, &args, env).map_err(|error| match tokens.peek() {
+        Some(next) if error.code() == Some(Code::UnexpectedEof) && error.span().is_none() => error
+            .map_diagnostic(|d| Diagnostic {
+                message: d.message.replace("<eof>", "declaration"),
+                ..d
+            })
+            .at(next.span()),
+        _ => error,
+    })?;
+
+    // Anything left before the next declaration usually means a call was given too many arguments
+    if let Some(next) = tokens.next_if(|t| t.kind() != TokenKind::Decl) {
+        let mut diagnostic = Diagnostic::error(format!(
+            "unexpected {} after the body of {name}",
+            next.kind()
+        ))
+        .with_code(Code::UnexpectedToken)
+        .with_label(next.span(), "expected a declaration")
+        .with_secondary(expr.span(), "the body ends here");
+
+        match last_call(&expr, closing_group(&consumed)) {
+            Expression::App(symbol, params, _) => {
+                diagnostic = diagnostic.with_help(format!(
+                    "{} takes {}, so the rest are not part of the call",
+                    env.get_name(*symbol),
+                    arguments(params.len())
+                ))
+            }
+            Expression::Arg(_, span) => {
+                // Locals bound by a `let` aren't parameters, so the name comes from the source
+                if let Some(Token::Name(local, _)) = consumed.iter().find(|t| t.span() == *span) {
+                    diagnostic = diagnostic
+                        .with_help(format!("{local} is a local, so calling it takes `apply`"))
+                }
+            }
+            _ => {}
+        }
+        return Err(diagnostic.into());
     }
 
+    let mut func = Function::new(args.len(), expr);
+    if let Some(annotation) = annotation {
+        func = func.with_annotation(annotation);
+    }
+    env.insert_function(name, func);
     Ok(())
 }
 
-pub fn parse_expr<'a>(
+/// The call `expr` ends with, which is the one that ran out of arguments when more follow it. A
+/// call in brackets, starting at `group`, already got all the arguments written inside them, so it
+/// is the call around it that ran out.
+fn last_call(expr: &Expression, group: Option<usize>) -> &Expression {
+    match expr {
+        Expression::App(_, params, span) => match params.last() {
+            Some(last @ Expression::App(..))
+                if last.span().end == span.end && Some(last.span().start) != group =>
+            {
+                last_call(last, group)
+            }
+            _ => expr,
+        },
+        Expression::Let(_, body, _) => last_call(body, group),
+        _ => expr,
+    }
+}
+
+/// Where the expression in brackets that `tokens` end with starts, if they end with one.
+fn closing_group(tokens: &[&Token]) -> Option<usize> {
+    let mut depth = 0;
+    for (i, token) in tokens.iter().enumerate().rev() {
+        match token.kind() {
+            TokenKind::RParen | TokenKind::RBracket | TokenKind::RBrace => depth += 1,
+            TokenKind::LParen | TokenKind::LBracket | TokenKind::LBrace => {
+                depth -= 1;
+                if depth == 0 {
+                    return tokens.get(i + 1).map(|token| token.span().start);
+                }
+            }
+            _ if depth == 0 => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Words of the tokens a declaration's parameters and types are made of.
+fn type_word<'a>(token: &Token<'a>) -> Option<&'a str> {
+    match token {
+        Token::Name(name, _) => Some(name),
+        Token::Arrow(_) => Some("->"),
+        Token::Colon(_) => Some(":"),
+        Token::LParen(_) => Some("("),
+        Token::RParen(_) => Some(")"),
+        _ => None,
+    }
+}
+
+/// The parameters of a declaration, with the types written on them, like `n: Num m`. An invalid
+/// type comes with the names of the parameters up to it, so that calls can still be parsed.
+#[allow(clippy::type_complexity)]
+fn parse_params<'a>(
+    types: &mut TypeParser<'a>,
+) -> core::result::Result<(Vec<&'a str>, Vec<Option<Type>>), (Vec<&'a str>, String)> {
+    let mut args = vec![];
+    let mut params = vec![];
+    while let Some(name) = types
+        .peek()
+        .filter(|w| !matches!(*w, "->" | ":" | "(" | ")"))
+    {
+        types.advance();
+        args.push(name);
+        params.push(if types.eat(":") {
+            match types.atom() {
+                Ok(ty) => Some(ty),
+                Err(message) => return Err((args, message)),
+            }
+        } else {
+            None
+        });
+    }
+    Ok((args, params))
+}
+
+/// The candidate closest to `name` by edit distance, if any is close enough to be a likely typo.
+/// Candidates that would need every char of `name` replaced have nothing in common with it.
+fn closest<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    let len = name.chars().count();
+    let limit = (len / 3).max(1);
+    candidates
+        .filter(|&candidate| candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= limit && distance < len)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Edit distance between `a` and `b` counted in chars, where swapping two neighbouring chars
+/// counts as a single edit since it is such a common typo.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let above = &rows[i - 1];
+            row[j] = (above[j - 1] + usize::from(a[i - 1] != b[j - 1]))
+                .min(above[j] + 1)
+                .min(row[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+
+    rows[a.len()][b.len()]
+}
+
+fn parse_match<'a>(
+    span: &Span,
     tokens: &mut impl Iterator<Item = &'a Token<'a>>,
     args: &Vec<&str>,
     env: &Environment,
 ) -> Result<Expression> {
-    let expr = match tokens
-        .next()
-        .ok_or_else(|| Error::General("expected expression, found <eof>".into()))?
+    let value = parse_expr(tokens, args, env)?;
+    let open = match next_token(tokens)? {
+        Token::LBracket(open) => open,
+        token => Err(Diagnostic::error(format!("expected `[`, found {}", token.kind()))
+            .with_code(Code::UnexpectedToken)
+            .with_label(token.span(), "expected `[`")
+            .with_secondary(span.clone(), "in this match")
+            .with_help("the arms of a match are written in brackets, as in `match n [0 -> \"zero\" _ -> \"other\"]`"))?,
+    };
+    let unclosed = |_| {
+        Error::from(
+            Diagnostic::error("unclosed `[`")
+                .with_code(Code::UnexpectedEof)
+                .with_label(open.clone(), "this `[` is never closed")
+                .with_help("add a `]` after the last arm"),
+        )
+    };
+
+    let mut arms = vec![];
+    loop {
+        let token = next_token(tokens).map_err(unclosed)?;
+        if let Token::RBracket(close) = token {
+            let span = span.start..close.end;
+            if arms.is_empty() {
+                Err(Diagnostic::error("match needs at least one arm")
+                    .with_code(Code::UnexpectedToken)
+                    .with_label(span.clone(), "nothing to match against"))?
+            }
+            return Ok(Expression::Match(Rc::new(value), arms.into(), span));
+        }
+
+        let mut scope = args.clone();
+        let bound = scope.len();
+        let pattern = parse_pattern(token, tokens, &mut scope, bound)?;
+
+        let mut guard = None;
+        let mut token = next_token(tokens).map_err(unclosed)?;
+        if let Token::Name("if", _) = token {
+            guard = Some(parse_expr(tokens, &scope, env)?);
+            token = next_token(tokens).map_err(unclosed)?;
+        }
+        if !matches!(token, Token::Arrow(_)) {
+            Err(
+                Diagnostic::error(format!("expected arrow, found {}", token.kind()))
+                    .with_code(Code::UnexpectedToken)
+                    .with_label(token.span(), "expected `->`")
+                    .with_secondary(pattern.span(), "after this pattern"),
+            )?
+        }
+
+        let body = parse_expr(tokens, &scope, env)?;
+        arms.push(Arm {
+            pattern,
+            guard,
+            body,
+        });
+    }
+}
+
+/// Parses a pattern, adding the names it binds to `scope`. Names from before `bound` are locals of
+/// the enclosing scope, which a pattern shadows like a `let` does.
+fn parse_pattern<'a: 's, 's>(
+    token: &'a Token<'a>,
+    tokens: &mut impl Iterator<Item = &'a Token<'a>>,
+    scope: &mut Vec<&'s str>,
+    bound: usize,
+) -> Result<Pattern> {
+    Ok(match token {
+        Token::Name("_", span) => Pattern::Wildcard(span.clone()),
+        Token::Name("true", span) => Pattern::Literal(Value::Bool(true), span.clone()),
+        Token::Name("false", span) => Pattern::Literal(Value::Bool(false), span.clone()),
+        Token::Name("none", span) => Pattern::Literal(Value::Nothing, span.clone()),
+        Token::Name(name, span) => {
+            if scope[bound..].contains(name) {
+                Err(
+                    Diagnostic::error(format!("{name} is bound more than once in this pattern"))
+                        .with_code(Code::UnexpectedToken)
+                        .with_label(span.clone(), "bound again here")
+                        .with_help("use `_` for parts that aren't needed"),
+                )?
+            }
+            scope.push(name);
+            Pattern::Bind(span.clone())
+        }
+        Token::Int(n, span) => Pattern::Literal(Value::Int(*n), span.clone()),
+        Token::Float(n, span) => Pattern::Literal(Value::Float(*n), span.clone()),
+        Token::String(s, span) => Pattern::Literal(Value::String(s.as_str().into()), span.clone()),
+        Token::LBracket(open) => {
+            let mut items = vec![];
+            let mut rest = None;
+            loop {
+                let token = next_token(tokens).map_err(|_| {
+                    Error::from(
+                        Diagnostic::error("unclosed `[`")
+                            .with_code(Code::UnexpectedEof)
+                            .with_label(open.clone(), "this `[` is never closed"),
+                    )
+                })?;
+                match token {
+                    Token::RBracket(close) => {
+                        break Pattern::List(items, rest, open.start..close.end);
+                    }
+                    Token::Colon(colon) if rest.is_none() => {
+                        if items.is_empty() {
+                            Err(Diagnostic::error("expected a pattern before `:`")
+                                .with_code(Code::UnexpectedToken)
+                                .with_label(colon.clone(), "")
+                                .with_help("`[x : rest]` matches a list starting with `x`"))?
+                        }
+                        let token = next_token(tokens)?;
+                        rest = Some(Box::new(parse_pattern(token, tokens, scope, bound)?));
+                    }
+                    token if rest.is_none() => {
+                        items.push(parse_pattern(token, tokens, scope, bound)?)
+                    }
+                    token => Err(Diagnostic::error(format!(
+                        "expected `]`, found {}",
+                        token.kind()
+                    ))
+                    .with_code(Code::UnexpectedToken)
+                    .with_label(token.span(), "expected `]`")
+                    .with_secondary(open.clone(), "the rest of this list was already matched"))?,
+                }
+            }
+        }
+        token => Err(
+            Diagnostic::error(format!("expected a pattern, found {}", token.kind()))
+                .with_code(Code::UnexpectedToken)
+                .with_label(token.span(), "expected a pattern"),
+        )?,
+    })
+}
+
+/// Parses expressions up to the `]` closing `open`, returning them and the span from `open` to the
+/// `]`.
+fn parse_bracketed<'a>(
+    open: &Span,
+    tokens: &mut impl Iterator<Item = &'a Token<'a>>,
+    args: &Vec<&str>,
+    env: &Environment,
+) -> Result<(Vec<Expression>, Span)> {
+    let mut items = vec![];
+    loop {
+        let token = next_token(tokens).map_err(|_| {
+            Error::from(
+                Diagnostic::error("unclosed `[`")
+                    .with_code(Code::UnexpectedEof)
+                    .with_label(open.clone(), "this `[` is never closed")
+                    .with_help("add a `]` after the last expression"),
+            )
+        })?;
+        match token {
+            Token::RBracket(close) => return Ok((items, open.start..close.end)),
+            token => items.push(parse_token(token, tokens, args, env)?),
+        }
+    }
+}
+
+/// How a builtin given more arguments than it takes in parentheses uses the rest.
+enum Variadic {
+    /// Functions combining two values into one of the same type, like `+`, are folded from the
+    /// left, so `(+ 1 2 3)` is `+ + 1 2 3`.
+    Fold,
+    /// Functions with only side effects, like `print`, are called on each argument in turn.
+    Each,
+}
+
+fn variadic(func: &Function) -> Option<Variadic> {
+    let ty = match (func.signature(), func.annotation()) {
+        (Some(signature), _) => signature.ty.clone(),
+        (None, Some(annotation)) => Type::function(
+            annotation
+                .params
+                .iter()
+                .cloned()
+                .collect::<Option<Vec<_>>>()?,
+            annotation.ret.clone()?,
+        ),
+        (None, None) => return None,
+    };
+
+    match (func.args(), ty) {
+        (2, Type::Fun(a, rest)) => match *rest {
+            Type::Fun(b, ret) if a == b && b == ret => Some(Variadic::Fold),
+            _ => None,
+        },
+        (1, Type::Fun(_, ret)) if *ret == Type::Nothing => Some(Variadic::Each),
+        _ => None,
+    }
+}
+
+/// Parses expressions up to the `)` closing `open`, returning them and the span of the `)`. A `_`
+/// is returned as `None`, since only a call of a named function can take it.
+fn parse_parenthesized<'a>(
+    open: &Span,
+    tokens: &mut impl Iterator<Item = &'a Token<'a>>,
+    args: &Vec<&str>,
+    env: &Environment,
+) -> Result<(Vec<Option<Expression>>, Span)> {
+    let mut items = vec![];
+    loop {
+        let token = next_token(tokens).map_err(|_| {
+            Error::from(
+                Diagnostic::error("unclosed `(`")
+                    .with_code(Code::UnexpectedEof)
+                    .with_label(open.clone(), "this `(` is never closed")
+                    .with_help("add a `)` after the last argument"),
+            )
+        })?;
+        match token {
+            Token::RParen(close) => return Ok((items, close.clone())),
+            Token::Name("_", _) if !args.contains(&"_") => items.push(None),
+            token => items.push(Some(parse_token(token, tokens, args, env)?)),
+        }
+    }
+}
+
+/// Parses what follows a `(`. A named function in parentheses takes all the arguments up to the
+/// `)`: fewer than it takes leaves the rest out, and more is only allowed for variadic builtins.
+/// Anything else is either just grouped, or called with the arguments after it.
+fn parse_group<'a>(
+    open: &Span,
+    tokens: &mut impl Iterator<Item = &'a Token<'a>>,
+    args: &Vec<&str>,
+    env: &Environment,
+) -> Result<Expression> {
+    let first = next_token(tokens)?;
+    let named = match first {
+        Token::Name(name, span) if !args.contains(name) => env
+            .get_entry(name)
+            .map(|(symbol, func)| (*name, span, symbol, func)),
+        _ => None,
+    };
+
+    let Some((name, name_span, symbol, func)) = named else {
+        let callee = match first {
+            Token::RParen(close) => Err(Diagnostic::error("empty parentheses")
+                .with_code(Code::UnexpectedToken)
+                .with_label(open.start..close.end, "expected an expression"))?,
+            token => parse_token(token, tokens, args, env)?,
+        };
+        let (params, close) = parse_parenthesized(open, tokens, args, env)?;
+        if params.is_empty() {
+            return Ok(callee);
+        }
+        let Some(params) = params.into_iter().collect::<Option<Vec<_>>>() else {
+            Err(
+                Diagnostic::error("`_` can only stand in for an argument of a function")
+                    .with_code(Code::MisplacedHole)
+                    .with_label(callee.span(), "not a named function")
+                    .with_help("use a lambda to leave out something else"),
+            )?
+        };
+        let span = callee.span().start..close.end;
+        return Ok(Expression::Call(Rc::new(callee), params.into(), span));
+    };
+
+    let (mut params, close) = parse_parenthesized(open, tokens, args, env)?;
+    let span = name_span.start..close.end;
+    let given = params.len();
+    if given <= func.args() {
+        params.resize(func.args(), None);
+        return Ok(match params.iter().all(Option::is_some) {
+            true => Expression::App(symbol, params.into_iter().flatten().collect(), span),
+            false => Expression::Partial(symbol, params.into(), span),
+        });
+    }
+
+    let Some(params) = params.into_iter().collect::<Option<Vec<_>>>() else {
+        Err(
+            Diagnostic::error("`_` can only leave out arguments a function takes")
+                .with_code(Code::MisplacedHole)
+                .with_label(
+                    span.clone(),
+                    format!("{name} takes {}, {given} given", arguments(func.args())),
+                )
+                .with_help("use a lambda to leave out something else"),
+        )?
+    };
+    match variadic(func) {
+        Some(Variadic::Fold) => {
+            let mut params = params.into_iter();
+            let first = params.next().unwrap();
+            Ok(params.fold(first, |acc, param| {
+                let span = name_span.start..param.span().end;
+                Expression::App(symbol, Rc::new([acc, param]), span)
+            }))
+        }
+        Some(Variadic::Each) => {
+            let calls = params
+                .into_iter()
+                .map(|param| {
+                    let span = name_span.start..param.span().end;
+                    Expression::App(symbol, Rc::new([param]), span)
+                })
+                .collect::<Vec<_>>();
+            Ok(Expression::Do(calls.into(), span))
+        }
+        None => Err(Diagnostic::error(format!(
+            "{name} takes {} but {given} were given",
+            arguments(func.args())
+        ))
+        .with_code(Code::TooManyArguments)
+        .with_label(span, format!("{name} takes {}", arguments(func.args())))
+        .with_help("only functions that combine two values of one type, like `+`, or that return nothing, like `print`, take any number of arguments")
+        .into()),
+    }
+}
+
+/// Parses an infix expression up to the first operator binding looser than `min`, or the `)` or
+/// `}` ending it, which is returned too. Operands are parsed as usual, apart from parentheses
+/// which group infix expressions, so `{fac n - 1}` is `- fac n 1`.
+fn parse_infix<'a>(
+    min: u16,
+    open: &Span,
+    tokens: &mut impl Iterator<Item = &'a Token<'a>>,
+    args: &Vec<&str>,
+    env: &Environment,
+) -> Result<(Expression, &'a Token<'a>)> {
+    let unclosed = |_| {
+        Error::from(
+            Diagnostic::error("unclosed delimiter")
+                .with_code(Code::UnexpectedEof)
+                .with_label(open.clone(), "this is never closed"),
+        )
+    };
+
+    let mut lhs = match next_token(tokens).map_err(unclosed)? {
+        Token::LParen(inner) => {
+            let (expr, close) = parse_infix(0, inner, tokens, args, env)?;
+            expect_close(inner, close, TokenKind::RParen)?;
+            expr
+        }
+        token => parse_token(token, tokens, args, env)?,
+    };
+
+    let mut next = next_token(tokens).map_err(unclosed)?;
+    loop {
+        let (name, span) = match next {
+            Token::RBrace(_) | Token::RParen(_) => return Ok((lhs, next)),
+            Token::Name(name, span) => (*name, span),
+            token => Err(Diagnostic::error(format!(
+                "expected an operator, found {}",
+                token.kind()
+            ))
+            .with_code(Code::UnexpectedToken)
+            .with_label(token.span(), "expected an operator")
+            .with_secondary(lhs.span(), "after this operand"))?,
+        };
+        let (Some(operator), Some((symbol, _))) = (env.operator(name), env.get_entry(name)) else {
+            let mut diagnostic = Diagnostic::error(format!("{name} is not an operator"))
+                .with_code(Code::InvalidOperator)
+                .with_label(span.clone(), "expected an operator")
+                .with_secondary(lhs.span(), "after this operand");
+            if env.get_function(name).map(Function::args) == Some(2) {
+                diagnostic = diagnostic.with_help(format!(
+                    "declare it with `\\infixl 6 {name}` to write it between its arguments"
+                ));
+            }
+            Err(diagnostic)?
+        };
+
+        let precedence = u16::from(operator.precedence);
+        if precedence < min {
+            return Ok((lhs, next));
+        }
+        let min = match operator.associativity {
+            Associativity::Right => precedence,
+            Associativity::Left | Associativity::None => precedence + 1,
+        };
+        let (rhs, after) = parse_infix(min, open, tokens, args, env)?;
+
+        if operator.associativity == Associativity::None {
+            if let Token::Name(other, other_span) = after {
+                if env.operator(*other) == Some(operator) {
+                    Err(
+                        Diagnostic::error(format!("{name} and {other} can't be chained"))
+                            .with_code(Code::InvalidOperator)
+                            .with_label(other_span.clone(), "")
+                            .with_secondary(span.clone(), "")
+                            .with_help("add parentheses to group them"),
+                    )?
+                }
+            }
+        }
+
+        let span = lhs.span().start..rhs.span().end;
+        lhs = Expression::App(symbol, Rc::new([lhs, rhs]), span);
+        next = after;
+    }
+}
+
+/// Checks that an infix expression ends with the delimiter matching the one it started with.
+fn expect_close(open: &Span, close: &Token, expected: TokenKind) -> Result<()> {
+    if close.kind() == expected {
+        return Ok(());
+    }
+    Err(
+        Diagnostic::error(format!("expected {expected}, found {}", close.kind()))
+            .with_code(Code::UnexpectedToken)
+            .with_label(close.span(), format!("expected {expected}"))
+            .with_secondary(open.clone(), "to close this")
+            .into(),
+    )
+}
+
+/// Points out that the name a `let` binds isn't in scope yet in the value it is bound to.
+fn unbound_in_value(error: Error, local: &str, local_span: &Span) -> Error {
+    if error.code() != Some(Code::UnknownFunction)
+        || !error.message().ends_with(&format!(" {local}"))
     {
+        return error;
+    }
+    error.map_diagnostic(|d| Diagnostic {
+        help: vec![],
+        ..d.with_secondary(local_span.clone(), format!("{local} is bound here"))
+            .with_note(format!(
+                "{local} is only in scope in the body of the let, after its value"
+            ))
+    })
+}
+
+/// `n` arguments, in the singular for one.
+fn arguments(n: usize) -> String {
+    match n {
+        1 => "1 argument".to_string(),
+        n => format!("{n} arguments"),
+    }
+}
+
+fn eof(expected: &str) -> Error {
+    Error::coded(
+        Code::UnexpectedEof,
+        format!("expected {expected}, found <eof>"),
+    )
+}
+
+fn next_token<'a>(tokens: &mut impl Iterator<Item = &'a Token<'a>>) -> Result<&'a Token<'a>> {
+    tokens.next().ok_or_else(|| eof("expression"))
+}
+
+pub fn parse_expr<'a>(
+    tokens: &mut impl Iterator<Item = &'a Token<'a>>,
+    args: &Vec<&str>,
+    env: &Environment,
+) -> Result<Expression> {
+    let token = next_token(tokens)?;
+    parse_token(token, tokens, args, env)
+}
+
+fn parse_token<'a>(
+    token: &'a Token<'a>,
+    tokens: &mut impl Iterator<Item = &'a Token<'a>>,
+    args: &Vec<&str>,
+    env: &Environment,
+) -> Result<Expression> {
+    // Every nested expression goes through here, so grow the stack instead of overflowing it
+    stacker::maybe_grow(STACK_RED_ZONE, STACK_GROWTH, || {
+        parse_token_(token, tokens, args, env)
+    })
+}
+
+fn parse_token_<'a>(
+    token: &'a Token<'a>,
+    tokens: &mut impl Iterator<Item = &'a Token<'a>>,
+    args: &Vec<&str>,
+    env: &Environment,
+) -> Result<Expression> {
+    let expr = match token {
         Token::Name(name, span) => {
-            if let Some(idx) = args.iter().position(|&a| a == *name) {
-                Expression::Arg(idx)
+            if let Some(idx) = args.iter().rposition(|&a| a == *name) {
+                Expression::Arg(idx, span.clone())
+            } else if *name == "apply" {
+                let callee = parse_expr(tokens, args, env)?;
+                let arg = parse_expr(tokens, args, env)?;
+                let span = span.start..arg.span().end;
+
+                // `apply apply f a b` passes both arguments to `f` at once
+                match callee {
+                    Expression::Call(callee, params, _) => {
+                        let mut params = params.to_vec();
+                        params.push(arg);
+                        Expression::Call(callee, params.into(), span)
+                    }
+                    callee => Expression::Call(Rc::new(callee), Rc::new([arg]), span),
+                }
+            } else if *name == "match" {
+                parse_match(span, tokens, args, env)?
+            } else if *name == "let" {
+                let (local, local_span) = match next_token(tokens)? {
+                    Token::Name(local, local_span) if !matches!(*local, "let" | "apply" | "_") => {
+                        (local, local_span)
+                    }
+                    token => Err(Diagnostic::error(format!(
+                        "expected a name to bind, found {}",
+                        token.kind()
+                    ))
+                    .with_code(Code::UnexpectedToken)
+                    .with_label(token.span(), "expected a name")
+                    .with_secondary(span.clone(), "in this let"))?,
+                };
+                // The value is parsed before the name is in scope, so it can't refer to itself
+                let value = parse_expr(tokens, args, env)
+                    .map_err(|e| unbound_in_value(e, local, local_span))?;
+
+                let mut scope = args.clone();
+                scope.push(local);
+                let body = parse_expr(tokens, &scope, env)?;
+                let span = span.start..body.span().end;
+                Expression::Let(Rc::new(value), Rc::new(body), span)
+            } else if *name == "do" {
+                let (exprs, end) = match next_token(tokens)? {
+                    Token::LBracket(open) => parse_bracketed(open, tokens, args, env)?,
+                    token => Err(Diagnostic::error(format!(
+                        "expected `[`, found {}",
+                        token.kind()
+                    ))
+                    .with_code(Code::UnexpectedToken)
+                    .with_label(token.span(), "expected `[`")
+                    .with_secondary(span.clone(), "in this do")
+                    .with_help("the expressions of a do are written in brackets, as in `do [print 1 print 2]`"))?,
+                };
+                let span = span.start..end.end;
+                if exprs.is_empty() {
+                    Err(Diagnostic::error("do needs at least one expression")
+                        .with_code(Code::UnexpectedToken)
+                        .with_label(span.clone(), "nothing to evaluate"))?
+                }
+                Expression::Do(exprs.into(), span)
+            } else if *name == "cond" {
+                let (branches, end) = match next_token(tokens)? {
+                    Token::LBracket(open) => parse_bracketed(open, tokens, args, env)?,
+                    token => Err(Diagnostic::error(format!(
+                        "expected `[`, found {}",
+                        token.kind()
+                    ))
+                    .with_code(Code::UnexpectedToken)
+                    .with_label(token.span(), "expected `[`")
+                    .with_secondary(span.clone(), "in this cond")
+                    .with_help("the branches of a cond are written in brackets, as in `cond [< n 0 \"negative\" \"positive\"]`"))?,
+                };
+                let span = span.start..end.end;
+                if branches.is_empty() {
+                    Err(Diagnostic::error("cond needs at least one branch")
+                        .with_code(Code::UnexpectedToken)
+                        .with_label(span.clone(), "nothing to choose from"))?
+                }
+                Expression::Cond(branches.into(), span)
+            } else if *name == "_" {
+                Err(
+                    Diagnostic::error("`_` can only stand in for an argument of a function")
+                        .with_code(Code::MisplacedHole)
+                        .with_label(span.clone(), "not an argument of a named function")
+                        .with_help("use a lambda to leave out something else"),
+                )?
             } else if let Some((symbol, func)) = env.get_entry(name) {
+                // Running out of arguments points out how many the function takes
+                let missing = |error: Error, given: usize| {
+                    error.map_diagnostic(|d| {
+                        d.with_secondary(
+                            span.clone(),
+                            format!("{name} takes {}, {given} given", arguments(func.args())),
+                        )
+                    })
+                };
+
+                // `_` leaves an argument out, making the call a partial application
                 let mut app_args = Vec::with_capacity(func.args());
-                for _ in 0..func.args() {
-                    app_args.push(parse_expr(tokens, args, env)?);
+                let mut end = span.end;
+                for given in 0..func.args() {
+                    let arg =
+                        match next_token(tokens).map_err(|e| missing(e, given))? {
+                            Token::Name("_", hole) => {
+                                end = hole.end;
+                                None
+                            }
+                            token => Some(parse_token(token, tokens, args, env).map_err(|e| {
+                                match e.code() {
+                                    Some(Code::UnexpectedToken)
+                                        if e.span() == Some(token.span()) =>
+                                    {
+                                        missing(e, given)
+                                    }
+                                    _ => e,
+                                }
+                            })?),
+                        };
+                    if let Some(arg) = &arg {
+                        end = arg.span().end;
+                    }
+                    app_args.push(arg);
                 }
 
-                Expression::App(symbol, app_args)
+                let span = span.start..end;
+                if app_args.iter().any(Option::is_none) {
+                    Expression::Partial(symbol, app_args.into(), span)
+                } else {
+                    Expression::App(symbol, app_args.into_iter().flatten().collect(), span)
+                }
             } else {
-                Err(Error::Spanned(
-                    format!("cannot find function or local {name}"),
-                    span.clone(),
-                ))?
+                let mut diagnostic =
+                    Diagnostic::error(format!("cannot find function or local {name}"))
+                        .with_code(Code::UnknownFunction)
+                        .with_label(span.clone(), "not found in this scope");
+
+                if let Some(local) = closest(name, args.iter().copied()) {
+                    diagnostic = diagnostic
+                        .with_help(format!("a local with a similar name exists: {local}"));
+                } else if let Some(function) = closest(name, env.names().chain(["apply"])) {
+                    diagnostic = diagnostic
+                        .with_help(format!("a function with a similar name exists: {function}"));
+                }
+                Err(diagnostic)?
+            }
+        }
+
+        Token::Ref(name, span) => match env.get_entry(name) {
+            Some((symbol, _)) => Expression::Ref(symbol, span.start - 1..span.end),
+            None => {
+                let mut diagnostic = Diagnostic::error(format!("cannot find function {name}"))
+                    .with_code(Code::UnknownFunction)
+                    .with_label(span.clone(), "not found");
+
+                if let Some(function) = closest(name, env.names()) {
+                    diagnostic = diagnostic
+                        .with_help(format!("a function with a similar name exists: {function}"));
+                } else if args.contains(name) {
+                    diagnostic = diagnostic.with_help(format!(
+                        "{name} is a local, which doesn't need a `'` to be passed on"
+                    ));
+                }
+                Err(diagnostic)?
+            }
+        },
+
+        Token::Lambda(name, span) => {
+            let mut scope = args.clone();
+            let outer = scope.len();
+            if !name.is_empty() {
+                scope.push(name);
+            }
+
+            loop {
+                match tokens.next() {
+                    Some(Token::Name(name, _)) => scope.push(name),
+                    Some(Token::Arrow(_)) => break,
+                    Some(token) => Err(Diagnostic::error(format!(
+                        "expected arrow, found {}",
+                        token.kind()
+                    ))
+                    .with_code(Code::UnexpectedToken)
+                    .with_label(token.span(), "expected `->`")
+                    .with_secondary(span.start - 1..span.end, "in this lambda"))?,
+                    None => Err(eof("arrow"))?,
+                }
             }
+
+            let body = parse_expr(tokens, &scope, env)?;
+            let span = span.start - 1..body.span().end;
+            Expression::Lambda(
+                Rc::new(Lambda {
+                    args: scope.len() - outer,
+                    body,
+                }),
+                span,
+            )
+        }
+
+        Token::LBracket(open) => {
+            let (items, span) = parse_bracketed(open, tokens, args, env)?;
+            Expression::List(items.into(), span)
         }
 
-        Token::Num(num, _) => Expression::Literal(Value::Num(*num)),
-        Token::String(str, _) => Expression::Literal(Value::String(str.to_string())),
-        token => Err(Error::Spanned(
-            format!("unexpected token {}", token.kind()),
-            token.span(),
-        ))?,
+        Token::LParen(open) => parse_group(open, tokens, args, env)?,
+
+        Token::LBrace(open) => {
+            let (expr, close) = parse_infix(0, open, tokens, args, env)?;
+            expect_close(open, close, TokenKind::RBrace)?;
+            expr
+        }
+
+        Token::Int(num, span) => Expression::Literal(Value::Int(*num), span.clone()),
+        Token::Float(num, span) => Expression::Literal(Value::Float(*num), span.clone()),
+        Token::String(str, span) => {
+            Expression::Literal(Value::String(str.as_str().into()), span.clone())
+        }
+        token => Err(
+            Diagnostic::error(format!("unexpected token {}", token.kind()))
+                .with_code(Code::UnexpectedToken)
+                .with_label(token.span(), "expected an expression"),
+        )?,
     };
     Ok(expr)
 }
@@ -1,3 +1,42 @@
 use std::ops::Range;
 
 pub type Span = Range<usize>;
+
+/// Every source an interpreter has read, laid out one after another so that spans from different
+/// sources never overlap and each span says which source it points into.
+#[derive(Debug, Default, Clone)]
+pub struct SourceMap {
+    /// Each source with the offset its spans start at, in order.
+    sources: Vec<(usize, String)>,
+}
+
+impl SourceMap {
+    /// A map of a single source whose spans start at zero.
+    pub fn single(text: &str) -> Self {
+        Self {
+            sources: vec![(0, text.to_string())],
+        }
+    }
+
+    /// Adds a source after the others, returning the offset its spans start at.
+    pub fn add(&mut self, text: &str) -> usize {
+        // Sources are a byte apart so a span at the end of one isn't also at the start of the next,
+        // and the first starts at one so the empty default span points into none of them
+        let base = self
+            .sources
+            .last()
+            .map_or(1, |(base, text)| base + text.len() + 1);
+        self.sources.push((base, text.to_string()));
+        base
+    }
+
+    /// The source `span` points into and the span relative to the start of it.
+    pub fn find(&self, span: &Span) -> Option<(&str, Span)> {
+        let index = self
+            .sources
+            .partition_point(|(base, _)| *base <= span.start)
+            .checked_sub(1)?;
+        let (base, text) = &self.sources[index];
+        (span.end <= base + text.len()).then(|| (&text[..], span.start - base..span.end - base))
+    }
+}
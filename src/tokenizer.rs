@@ -1,28 +1,74 @@
+use std::{iter::Peekable, num::IntErrorKind, str::CharIndices};
+
 use crate::{
+    diagnostic::{Code, Diagnostic},
     error::{Error, Result},
     span::Span,
 };
 
 #[derive(Clone, Debug)]
 pub enum Token<'a> {
+    /// A `\` at the start of a line, any other one begins a lambda.
     Decl(&'a str, Span),
+    Lambda(&'a str, Span),
     Name(&'a str, Span),
+    Ref(&'a str, Span),
     String(String, Span),
-    Num(u64, Span), // Only natural number support for now
+    Int(i64, Span),
+    Float(f64, Span),
     Arrow(Span),
+    Colon(Span),
+    LParen(Span),
+    RParen(Span),
+    LBracket(Span),
+    RBracket(Span),
+    LBrace(Span),
+    RBrace(Span),
 }
 
 impl Token<'_> {
     pub fn span(&self) -> Span {
         match self {
             Self::Decl(_, s)
+            | Self::Lambda(_, s)
             | Self::Name(_, s)
+            | Self::Ref(_, s)
             | Self::String(_, s)
-            | Self::Num(_, s)
-            | Self::Arrow(s) => s.clone(),
+            | Self::Int(_, s)
+            | Self::Float(_, s)
+            | Self::Arrow(s)
+            | Self::Colon(s)
+            | Self::LParen(s)
+            | Self::RParen(s)
+            | Self::LBracket(s)
+            | Self::RBracket(s)
+            | Self::LBrace(s)
+            | Self::RBrace(s) => s.clone(),
         }
     }
 
+    /// The token moved `offset` bytes later, for a source that doesn't start at zero.
+    pub fn offset(mut self, offset: usize) -> Self {
+        match &mut self {
+            Self::Decl(_, s)
+            | Self::Lambda(_, s)
+            | Self::Name(_, s)
+            | Self::Ref(_, s)
+            | Self::String(_, s)
+            | Self::Int(_, s)
+            | Self::Float(_, s)
+            | Self::Arrow(s)
+            | Self::Colon(s)
+            | Self::LParen(s)
+            | Self::RParen(s)
+            | Self::LBracket(s)
+            | Self::RBracket(s)
+            | Self::LBrace(s)
+            | Self::RBrace(s) => *s = s.start + offset..s.end + offset,
+        }
+        self
+    }
+
     pub fn kind(&self) -> TokenKind {
         TokenKind::from(self)
     }
@@ -31,20 +77,40 @@ impl Token<'_> {
 #[derive(Debug, PartialEq, Eq)]
 pub enum TokenKind {
     Decl,
+    Lambda,
     Name,
+    Ref,
     String,
-    Num,
+    Int,
+    Float,
     Arrow,
+    Colon,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
 }
 
 impl From<&Token<'_>> for TokenKind {
     fn from(token: &Token) -> Self {
         match token {
             Token::Decl(_, _) => Self::Decl,
+            Token::Lambda(_, _) => Self::Lambda,
             Token::Name(_, _) => Self::Name,
+            Token::Ref(_, _) => Self::Ref,
             Token::String(_, _) => Self::String,
-            Token::Num(_, _) => Self::Num,
+            Token::Int(_, _) => Self::Int,
+            Token::Float(_, _) => Self::Float,
             Token::Arrow(_) => Self::Arrow,
+            Token::Colon(_) => Self::Colon,
+            Token::LParen(_) => Self::LParen,
+            Token::RParen(_) => Self::RParen,
+            Token::LBracket(_) => Self::LBracket,
+            Token::RBracket(_) => Self::RBracket,
+            Token::LBrace(_) => Self::LBrace,
+            Token::RBrace(_) => Self::RBrace,
         }
     }
 }
@@ -56,48 +122,114 @@ impl std::fmt::Display for TokenKind {
             "{}",
             match self {
                 Self::Decl => "declaration",
+                Self::Lambda => "lambda",
                 Self::Name => "name",
+                Self::Ref => "function reference",
                 Self::String => "string",
-                Self::Num => "num",
+                Self::Int => "int",
+                Self::Float => "float",
                 Self::Arrow => "<arrow>",
+                Self::Colon => "`:`",
+                Self::LParen => "`(`",
+                Self::RParen => "`)`",
+                Self::LBracket => "`[`",
+                Self::RBracket => "`]`",
+                Self::LBrace => "`{`",
+                Self::RBrace => "`}`",
             }
         )
     }
 }
 
-pub fn tokenize(src: &str) -> Result<Vec<Token>> {
+/// Consumes the rest of a word, which ends at whitespace or punctuation, returning the byte offset
+/// of its end.
+fn skip_word(chars: &mut Peekable<CharIndices>, mut end: usize) -> usize {
+    while let Some((i, c)) = chars.next_if(|(_, next)| {
+        !next.is_whitespace() && !matches!(next, ':' | '(' | ')' | '[' | ']' | '{' | '}')
+    }) {
+        end = i + c.len_utf8();
+    }
+    end
+}
+
+fn invalid_token(message: &str, span: Span) -> Error {
+    Diagnostic::error(message)
+        .with_code(Code::InvalidToken)
+        .with_label(span, "")
+        .into()
+}
+
+/// Integer literals are plain digits, anything with a decimal point or an exponent is a float.
+fn number(literal: &str, span: Span) -> Result<Token<'_>> {
+    if literal.contains(['.', 'e', 'E']) {
+        // Rust parses literals too big for a float as infinity, which `str->num` doesn't accept either
+        match literal.parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(Token::Float(n, span)),
+            Ok(_) => Err(invalid_token("float literal out of range", span)),
+            Err(_) => Err(invalid_token("invalid number literal", span)),
+        }
+    } else {
+        literal
+            .parse::<i64>()
+            .map(|n| Token::Int(n, span.clone()))
+            .map_err(|e| {
+                let message = match e.kind() {
+                    IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => {
+                        "integer literal out of range"
+                    }
+                    _ => "invalid number literal",
+                };
+                invalid_token(message, span)
+            })
+    }
+}
+
+/// Tokenizes a file, where a `\` at the start of a line begins a declaration.
+pub fn tokenize(src: &str) -> Result<Vec<Token<'_>>> {
+    tokens(src, true)
+}
+
+/// Tokenizes a single expression, like a line typed into the repl, where every `\` begins a
+/// lambda.
+pub fn tokenize_expr(src: &str) -> Result<Vec<Token<'_>>> {
+    tokens(src, false)
+}
+
+fn tokens(src: &str, declarations: bool) -> Result<Vec<Token<'_>>> {
     let mut tokens = vec![];
-    let mut chars = src.chars().enumerate().peekable();
+    let mut chars = src.char_indices().peekable();
 
     while let Some((i, c)) = chars.next() {
         tokens.push(match c {
             '0'..='9' => {
-                let mut end = i + 1;
-                while chars.next_if(|(_, next)| !next.is_whitespace()).is_some() {
-                    end += 1;
-                }
-
-                src[i..end]
-                    .parse::<u64>()
-                    .map(|n| Token::Num(n, i..end))
-                    .map_err(|_| Error::Spanned("invalid number literal".into(), i..end))?
+                let end = skip_word(&mut chars, i + 1);
+                number(&src[i..end], i..end)?
             }
 
             ' ' | '\t' | '\n' | '\r' => continue,
 
-            '-' => {
-                match chars.peek() {
-                    Some((_, '>')) => {
-                        let _ = chars.next();
-                        Token::Arrow(i..i + 2)
-                    }
-                    Some((_, ' ' | '\t' | '\n' | '\r')) => {
-                        let _ = chars.next();
-                        Token::Name("-", i..i + 1)
-                    }
-                    _ => panic!(), // Bad but whatever
+            ':' => Token::Colon(i..i + 1),
+            '(' => Token::LParen(i..i + 1),
+            ')' => Token::RParen(i..i + 1),
+            '[' => Token::LBracket(i..i + 1),
+            ']' => Token::RBracket(i..i + 1),
+            '{' => Token::LBrace(i..i + 1),
+            '}' => Token::RBrace(i..i + 1),
+
+            '-' => match chars.peek() {
+                Some((_, '>')) => {
+                    let _ = chars.next();
+                    Token::Arrow(i..i + 2)
                 }
-            }
+                Some((_, '0'..='9' | '.')) => {
+                    let end = skip_word(&mut chars, i + 1);
+                    number(&src[i..end], i..end)?
+                }
+                _ => {
+                    let end = skip_word(&mut chars, i + 1);
+                    Token::Name(&src[i..end], i..end)
+                }
+            },
 
             '#' => {
                 while chars.next_if(|&(_, c)| c != '\n').is_some() {}
@@ -107,7 +239,8 @@ pub fn tokenize(src: &str) -> Result<Vec<Token>> {
             '\"' => {
                 let mut literal = String::new(); // Temporary string to hold the parsed literal
                 let mut escaped = false;
-                while let Some((i, ch)) = chars.next() {
+                let mut end = None;
+                for (j, ch) in chars.by_ref() {
                     if escaped {
                         match ch {
                             'n' => literal.push('\n'),
@@ -115,42 +248,41 @@ pub fn tokenize(src: &str) -> Result<Vec<Token>> {
                             '\"' => literal.push('\"'),
                             '\\' => literal.push('\\'),
                             _ => {
-                                dbg!(ch);
-                                return Err(Error::Spanned(
-                                    "invalid escape sequence".into(),
-                                    i..i + 2,
-                                ));
+                                return Err(Diagnostic::error("invalid escape sequence")
+                                    .with_code(Code::InvalidToken)
+                                    .with_label(j - 1..j + ch.len_utf8(), "")
+                                    .with_note("the escape sequences are \\n, \\t, \\\" and \\\\")
+                                    .into());
                             } // Handle unexpected escape sequences
                         }
                         escaped = false;
                     } else if ch == '\\' {
                         escaped = true; // Next character is escaped
                     } else if ch == '\"' {
+                        end = Some(j + 1);
                         break; // End of string literal
                     } else {
                         literal.push(ch);
                     }
                 }
-                if escaped {
-                    // Handle case where the last character is a backslash, which is invalid
-                    return Err(Error::Spanned(
-                        "unfinished escape sequence".into(),
-                        i..i + 1,
-                    ));
-                }
-                let len = literal.len();
-                Token::String(literal, i..i + 2 + len)
+                let Some(end) = end else {
+                    return Err(Diagnostic::error("unterminated string literal")
+                        .with_code(Code::InvalidToken)
+                        .with_label(i..src.len(), "string is never closed")
+                        .into());
+                };
+                Token::String(literal, i..end)
             }
 
             _ => {
-                let mut end = i + 1;
-
-                while chars.next_if(|(_, next)| !next.is_whitespace()).is_some() {
-                    end += 1;
-                }
+                let end = skip_word(&mut chars, i + c.len_utf8());
 
-                if c == '\\' {
+                if c == '\\' && declarations && (i == 0 || src[..i].ends_with('\n')) {
                     Token::Decl(&src[i + 1..end], i + 1..end)
+                } else if c == '\\' {
+                    Token::Lambda(&src[i + 1..end], i + 1..end)
+                } else if c == '\'' {
+                    Token::Ref(&src[i + 1..end], i + 1..end)
                 } else {
                     Token::Name(&src[i..end], i..end)
                 }
@@ -160,3 +292,39 @@ pub fn tokenize(src: &str) -> Result<Vec<Token>> {
 
     Ok(tokens)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        let tokens = tokenize("42 -7 1.5 -0.25 6e2 -x").unwrap();
+        assert!(matches!(tokens[0], Token::Int(42, _)));
+        assert!(matches!(tokens[1], Token::Int(-7, _)));
+        assert!(matches!(tokens[2], Token::Float(n, _) if n == 1.5));
+        assert!(matches!(tokens[3], Token::Float(n, _) if n == -0.25));
+        assert!(matches!(tokens[4], Token::Float(n, _) if n == 600.0));
+        assert!(matches!(tokens[5], Token::Name("-x", _)));
+    }
+
+    #[test]
+    fn declarations_and_lambdas() {
+        let tokens = tokenize("\\id x -> \\y -> y\n\\main -> 'id").unwrap();
+        assert!(matches!(tokens[0], Token::Decl("id", _)));
+        assert!(matches!(tokens[3], Token::Lambda("y", _)));
+        assert!(matches!(tokens[6], Token::Decl("main", _)));
+        assert!(matches!(tokens[8], Token::Ref("id", _)));
+
+        // An expression has no declarations, so it can start with a lambda
+        let tokens = tokenize_expr("\\x -> x").unwrap();
+        assert!(matches!(tokens[0], Token::Lambda("x", _)));
+    }
+
+    #[test]
+    fn invalid_numbers() {
+        for src in ["99999999999999999999", "1e400", "1.2.3", "12abc"] {
+            assert!(tokenize(src).is_err(), "{src} should not tokenize");
+        }
+    }
+}
@@ -0,0 +1,456 @@
+use std::{collections::HashMap, fmt, str::FromStr};
+
+use crate::{interpreter::Value, span::Span};
+
+pub type TypeVar = usize;
+
+/// A static type. Both kinds of numbers share `Num`, since arithmetic mixes them freely.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Num,
+    String,
+    Bool,
+    Nothing,
+    /// A list whose elements all have the given type.
+    List(Box<Type>),
+    /// A function taking one argument, so that functions of several arguments are curried the way
+    /// `apply` passes them.
+    Fun(Box<Type>, Box<Type>),
+    Var(TypeVar),
+}
+
+impl Type {
+    /// The type of a function taking `params` and returning `ret`, which is just `ret` if it takes
+    /// no arguments.
+    pub fn function(params: impl IntoIterator<Item = Type>, ret: Type) -> Self {
+        let params = params.into_iter().collect::<Vec<_>>();
+        params
+            .into_iter()
+            .rev()
+            .fold(ret, |ret, param| Self::Fun(Box::new(param), Box::new(ret)))
+    }
+
+    pub fn list(element: Type) -> Self {
+        Self::List(Box::new(element))
+    }
+
+    /// Type variables in the order they first appear.
+    pub fn vars(&self) -> Vec<TypeVar> {
+        let mut vars = vec![];
+        self.collect_vars(&mut vars);
+        vars
+    }
+
+    fn collect_vars(&self, vars: &mut Vec<TypeVar>) {
+        match self {
+            Self::Var(var) if !vars.contains(var) => vars.push(*var),
+            Self::List(element) => element.collect_vars(vars),
+            Self::Fun(param, ret) => {
+                param.collect_vars(vars);
+                ret.collect_vars(vars);
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether `value` belongs to the type, as far as can be told without looking into functions.
+    pub fn admits(&self, value: &Value) -> bool {
+        match (self, value) {
+            (Self::List(element), Value::List(items)) => {
+                items.iter().all(|item| element.admits(item))
+            }
+            _ => matches!(
+                (self, value),
+                (Self::Num, Value::Int(_) | Value::Float(_))
+                    | (Self::String, Value::String(_))
+                    | (Self::Bool, Value::Bool(_))
+                    | (Self::Nothing, Value::Nothing)
+                    | (Self::Fun(..), Value::Function(_))
+                    | (Self::Var(_), _)
+            ),
+        }
+    }
+
+    /// Writes the type as the parameter of a function type, in parentheses if it is one itself.
+    fn write_param(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        names: &HashMap<TypeVar, String>,
+    ) -> fmt::Result {
+        if let Self::Fun(..) = self {
+            write!(f, "(")?;
+            self.write(f, names)?;
+            write!(f, ")")
+        } else {
+            self.write(f, names)
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, names: &HashMap<TypeVar, String>) -> fmt::Result {
+        match self {
+            Self::Num => write!(f, "Num"),
+            Self::String => write!(f, "String"),
+            Self::Bool => write!(f, "Bool"),
+            Self::Nothing => write!(f, "Nothing"),
+            Self::List(element) => {
+                write!(f, "List ")?;
+                if let Self::Fun(..) | Self::List(_) = **element {
+                    write!(f, "(")?;
+                    element.write(f, names)?;
+                    write!(f, ")")
+                } else {
+                    element.write(f, names)
+                }
+            }
+            Self::Fun(param, ret) => {
+                param.write_param(f, names)?;
+                write!(f, " -> ")?;
+                ret.write(f, names)
+            }
+            Self::Var(var) => match names.get(var) {
+                Some(name) => write!(f, "{name}"),
+                None => write!(f, "t{var}"),
+            },
+        }
+    }
+}
+
+/// A type whose variables can stand for a different type at each use, such as `a -> a` for a
+/// function returning its argument. Some variables are limited to a few types, like the numbers
+/// and strings that `+` works on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scheme {
+    pub vars: Vec<TypeVar>,
+    pub constraints: HashMap<TypeVar, Vec<Type>>,
+    pub ty: Type,
+}
+
+impl Scheme {
+    /// A scheme for `ty` generalized over all of its variables.
+    pub fn new(ty: Type, constraints: HashMap<TypeVar, Vec<Type>>) -> Self {
+        let vars = ty.vars();
+        let constraints = constraints
+            .into_iter()
+            .filter(|(var, _)| vars.contains(var))
+            .collect();
+        Self {
+            vars,
+            constraints,
+            ty,
+        }
+    }
+
+    /// A scheme for `ty` that isn't generalized, so its variables stand for the same type at each
+    /// use, as for a parameter.
+    pub fn mono(ty: Type) -> Self {
+        Self {
+            vars: vec![],
+            constraints: HashMap::new(),
+            ty,
+        }
+    }
+
+    fn names(&self) -> HashMap<TypeVar, String> {
+        names(self.ty.vars())
+    }
+}
+
+/// Names for type variables, `a`, `b` and so on in the order given.
+fn names(vars: Vec<TypeVar>) -> HashMap<TypeVar, String> {
+    vars.into_iter()
+        .enumerate()
+        .map(|(i, var)| {
+            let letter = char::from(b'a' + (i % 26) as u8);
+            let name = match i / 26 {
+                0 => letter.to_string(),
+                n => format!("{letter}{n}"),
+            };
+            (var, name)
+        })
+        .collect()
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Scheme::new(self.clone(), HashMap::new()))
+    }
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = self.names();
+        self.ty.write(f, &names)?;
+
+        let mut constrained = self
+            .ty
+            .vars()
+            .into_iter()
+            .filter_map(|var| Some((var, self.constraints.get(&var)?)))
+            .peekable();
+        if constrained.peek().is_some() {
+            write!(f, " where")?;
+        }
+        while let Some((var, allowed)) = constrained.next() {
+            write!(f, " {}:", names[&var])?;
+            for (i, ty) in allowed.iter().enumerate() {
+                write!(f, "{}", if i == 0 { " " } else { " | " })?;
+                ty.write(f, &names)?;
+            }
+            if constrained.peek().is_some() {
+                write!(f, ",")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parses signatures like `a -> a -> a where a: Num | String`, as written for the builtins.
+impl FromStr for Scheme {
+    type Err = String;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let words = split(src);
+        let mut parser = TypeParser::new(&words);
+
+        let ty = parser.ty()?;
+        let mut constraints = HashMap::new();
+        if parser.eat("where") {
+            loop {
+                let var = match parser.advance() {
+                    Some(name) if is_var(name) => parser.var(name),
+                    found => return Err(format!("expected type variable, found {found:?}")),
+                };
+                if !parser.eat(":") {
+                    return Err("expected `:` after type variable".into());
+                }
+
+                let mut allowed = vec![parser.ty()?];
+                while parser.eat("|") {
+                    allowed.push(parser.ty()?);
+                }
+                constraints.insert(var, allowed);
+
+                if !parser.eat(",") {
+                    break;
+                }
+            }
+        }
+
+        match parser.advance() {
+            None => Ok(Self::new(ty, constraints)),
+            Some(word) => Err(format!("unexpected `{word}` in type")),
+        }
+    }
+}
+
+/// Splits a signature into names and punctuation.
+fn split(src: &str) -> Vec<&str> {
+    let mut words = vec![];
+    let mut rest = src.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if rest.starts_with("->") {
+            2
+        } else if c.is_alphanumeric() || c == '_' {
+            rest.find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(rest.len())
+        } else {
+            c.len_utf8()
+        };
+        words.push(&rest[..len]);
+        rest = rest[len..].trim_start();
+    }
+    words
+}
+
+fn is_var(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_lowercase()) && name != "where"
+}
+
+/// Parses types out of a list of words, which are names, `->` and parentheses. Type variables are
+/// numbered by name, so that they stay the same across everything parsed with one parser.
+pub struct TypeParser<'a> {
+    words: &'a [&'a str],
+    pos: usize,
+    vars: HashMap<&'a str, TypeVar>,
+}
+
+impl<'a> TypeParser<'a> {
+    pub fn new(words: &'a [&'a str]) -> Self {
+        Self {
+            words,
+            pos: 0,
+            vars: HashMap::new(),
+        }
+    }
+
+    /// How many words have been parsed.
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn peek(&self) -> Option<&'a str> {
+        self.words.get(self.pos).copied()
+    }
+
+    pub fn advance(&mut self) -> Option<&'a str> {
+        let word = self.peek()?;
+        self.pos += 1;
+        Some(word)
+    }
+
+    /// Goes back to an earlier position, to parse the words there as something else.
+    pub fn rewind(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    /// Skips over `word` if it is next.
+    pub fn eat(&mut self, word: &str) -> bool {
+        let found = self.peek() == Some(word);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn var(&mut self, name: &'a str) -> TypeVar {
+        let next = self.vars.len();
+        *self.vars.entry(name).or_insert(next)
+    }
+
+    /// A whole type, which may be a function type.
+    pub fn ty(&mut self) -> Result<Type, String> {
+        let param = self.atom()?;
+        if self.eat("->") {
+            let ret = self.ty()?;
+            Ok(Type::Fun(Box::new(param), Box::new(ret)))
+        } else {
+            Ok(param)
+        }
+    }
+
+    /// A type that doesn't need parentheses around it to be the parameter of a function type, which
+    /// is any but a function type. The element type of a list is itself an atom, so a list of
+    /// lists is written `List (List a)`.
+    pub fn atom(&mut self) -> Result<Type, String> {
+        Ok(match self.advance() {
+            Some("(") => {
+                let ty = self.ty()?;
+                if !self.eat(")") {
+                    return Err("expected `)`".into());
+                }
+                ty
+            }
+            Some("Num") => Type::Num,
+            Some("String") => Type::String,
+            Some("Bool") => Type::Bool,
+            Some("Nothing") => Type::Nothing,
+            Some("List") => match self.atom() {
+                Ok(element) => Type::list(element),
+                Err(_) => return Err("expected the element type of List, as in `List a`".into()),
+            },
+            Some(name) if is_var(name) => Type::Var(self.var(name)),
+            Some(name) => {
+                self.pos -= 1;
+                return Err(format!("unknown type {name}"));
+            }
+            None => return Err("expected type".into()),
+        })
+    }
+}
+
+/// Types written on the parameters and result of a declaration, like `\fac n: Num -> Num -> ...`,
+/// any of which may be left out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub params: Vec<Option<Type>>,
+    pub ret: Option<Type>,
+    /// The declaration the types are written on.
+    pub span: Span,
+}
+
+impl Annotation {
+    /// Type variables of all the annotated types, in the order they first appear.
+    pub fn vars(&self) -> Vec<TypeVar> {
+        let mut vars = vec![];
+        for ty in self.params.iter().chain([&self.ret]).flatten() {
+            ty.collect_vars(&mut vars);
+        }
+        vars
+    }
+}
+
+impl fmt::Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = names(self.vars());
+
+        for param in &self.params {
+            match param {
+                Some(ty) => ty.write_param(f, &names)?,
+                None => write!(f, "_")?,
+            }
+            write!(f, " -> ")?;
+        }
+        match &self.ret {
+            Some(ty) => ty.write(f, &names),
+            None => write!(f, "_"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::default_env;
+
+    fn scheme(src: &str) -> String {
+        src.parse::<Scheme>().unwrap().to_string()
+    }
+
+    #[test]
+    fn signatures_round_trip() {
+        assert_eq!(scheme("Num -> Num -> Bool"), "Num -> Num -> Bool");
+        assert_eq!(
+            scheme("(a -> b) -> List a -> List b"),
+            "(a -> b) -> List a -> List b"
+        );
+        assert_eq!(scheme("List (List a) -> List a"), "List (List a) -> List a");
+        assert_eq!(
+            scheme("a -> a -> a where a: Num | String"),
+            "a -> a -> a where a: Num | String"
+        );
+    }
+
+    #[test]
+    fn invalid_signatures() {
+        for src in [
+            "Num ->",
+            "List",
+            "a where b",
+            "a where a Num",
+            "Num Num",
+            "(Num",
+        ] {
+            assert!(src.parse::<Scheme>().is_err(), "{src} should not parse");
+        }
+    }
+
+    #[test]
+    fn builtin_signatures_match_arity() {
+        let env = default_env();
+        for (symbol, func) in env.functions() {
+            let mut ty = &func.signature().unwrap().ty;
+            let mut params = 0;
+            while let Type::Fun(_, ret) = ty {
+                params += 1;
+                ty = ret;
+            }
+            // A builtin can return a function, but never take fewer arguments than its type says
+            assert!(
+                params >= func.args(),
+                "{} takes {} arguments but its signature has {params}",
+                env.get_name(symbol),
+                func.args()
+            );
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use f::{Code, Interpreter, Value};
+
+fn eval(src: &str) -> f::Result<Value> {
+    Interpreter::new().eval(src)
+}
+
+#[test]
+fn logic() {
+    let cases = [
+        ("and true false", false),
+        ("and true true", true),
+        ("or false true", true),
+        ("or false false", false),
+        ("not false", true),
+        ("xor true true", false),
+        ("xor true false", true),
+    ];
+    for (src, expected) in cases {
+        assert_eq!(eval(src).unwrap(), Value::Bool(expected), "{src}");
+    }
+}
+
+#[test]
+fn and_or_short_circuit() {
+    assert_eq!(eval("and false head []").unwrap(), Value::Bool(false));
+    assert_eq!(eval("or true head []").unwrap(), Value::Bool(true));
+    assert_eq!(
+        eval("and true head []").unwrap_err().code(),
+        Some(Code::EmptyList)
+    );
+}
+
+#[test]
+fn cond_picks_the_first_true_branch() {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_type_check(true);
+    interpreter
+        .load(r#"\sign n -> cond [< n 0 "negative" = n 0 "zero" "positive"]"#)
+        .unwrap();
+    for (n, expected) in [(-3, "negative"), (0, "zero"), (5, "positive")] {
+        let result = interpreter.call("sign", vec![Value::Int(n)]).unwrap();
+        assert_eq!(result.to_string(), expected);
+    }
+
+    // Only the chosen result is evaluated
+    assert_eq!(
+        eval("cond [false head [] true 2 head []]").unwrap(),
+        Value::Int(2)
+    );
+}
+
+#[test]
+fn cond_errors() {
+    let cases = [
+        ("cond [false 1]", Code::NoMatch),
+        ("cond [1 2 3]", Code::WrongType),
+        ("cond []", Code::UnexpectedToken),
+        ("apply 'cond [true 1]", Code::UnknownFunction),
+    ];
+    for (src, code) in cases {
+        assert_eq!(eval(src).unwrap_err().code(), Some(code), "{src}");
+    }
+}
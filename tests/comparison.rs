@@ -0,0 +1,38 @@
+use f::{Code, Interpreter, Value};
+
+fn eval(src: &str) -> f::Result<Value> {
+    Interpreter::new().eval(src)
+}
+
+#[test]
+fn every_kind_compares() {
+    let cases = [
+        ("= 1 1.0", true),
+        ("!= 1 2", true),
+        (r#"< "a" "b""#, true),
+        (r#"< "b" "ab""#, false),
+        ("< [1 2] [1 3]", true),
+        ("< [1] [1 0]", true),
+        ("< false true", true),
+        (">= 2 2", true),
+        ("<= 3 2", false),
+        ("> 2.5 2", true),
+        ("= [1 [2]] [1 [2]]", true),
+        ("= 'head 'head", true),
+        ("= none none", true),
+    ];
+    for (src, expected) in cases {
+        assert_eq!(eval(src).unwrap(), Value::Bool(expected), "{src}");
+    }
+}
+
+#[test]
+fn different_kinds_are_an_error() {
+    for src in [r#"< 1 "a""#, r#"= 1 "a""#, "= [1] [true]", "< 'head 'head"] {
+        assert_eq!(
+            eval(src).unwrap_err().code(),
+            Some(Code::WrongType),
+            "{src}"
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use f::{Code, Interpreter, Value};
+
+#[test]
+fn load_and_call() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .load(r"\fac n -> if = n 0 1 * n fac - n 1")
+        .unwrap();
+    let result = interpreter.call("fac", vec![Value::Int(5)]).unwrap();
+    assert_eq!(result, Value::Int(120));
+}
+
+#[test]
+fn failed_load_leaves_environment_unchanged() {
+    let mut interpreter = Interpreter::new();
+    interpreter.load(r"\f a -> a").unwrap();
+    assert!(interpreter.load("\\f a b -> b\n\\g -> missing").is_err());
+
+    assert!(interpreter.env().get_function("g").is_none());
+    let result = interpreter.call("f", vec![Value::Int(1)]).unwrap();
+    assert_eq!(result, Value::Int(1));
+}
+
+#[test]
+fn reload_cannot_change_arity_under_callers() {
+    let mut interpreter = Interpreter::new();
+    interpreter.load("\\f a -> a\n\\g -> f 1").unwrap();
+
+    let error = interpreter.load(r"\f a b -> b").unwrap_err();
+    assert_eq!(error.code(), Some(Code::ArityChanged));
+    assert_eq!(interpreter.call("g", vec![]).unwrap(), Value::Int(1));
+}
+
+#[test]
+fn stale_function_values_fail_instead_of_panicking() {
+    let mut interpreter = Interpreter::new();
+    interpreter.load(r"\f a -> a").unwrap();
+    let closure = interpreter.eval(r"(\x -> f x)").unwrap();
+    let partial = interpreter.eval("(f _)").unwrap();
+
+    interpreter.load(r"\f a b -> b").unwrap();
+    for func in [closure, partial] {
+        let error = interpreter.apply(&func, vec![Value::Int(1)]).unwrap_err();
+        assert_eq!(error.code(), Some(Code::ArityChanged));
+    }
+}
+
+#[test]
+fn errors_point_into_the_source_they_happened_in() {
+    let mut interpreter = Interpreter::new();
+    interpreter.load("\\bad xs -> head xs").unwrap();
+    let err = interpreter.eval("bad []").unwrap_err();
+    let (file, span) = interpreter.sources().find(&err.span().unwrap()).unwrap();
+    assert_eq!(file, "\\bad xs -> head xs");
+    assert_eq!(&file[span], "head xs");
+}
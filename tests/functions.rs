@@ -0,0 +1,36 @@
+use f::{Interpreter, Value};
+
+fn interpreter(src: &str) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.load(src).unwrap();
+    interpreter
+}
+
+#[test]
+fn lambdas_capture_locals() {
+    let interpreter = interpreter(r"\adder n -> \x -> + x n");
+    let add = interpreter.call("adder", vec![Value::Int(2)]).unwrap();
+    let result = interpreter.apply(&add, vec![Value::Int(3)]).unwrap();
+    assert_eq!(result, Value::Int(5));
+}
+
+#[test]
+fn references_and_apply() {
+    let mut interpreter = interpreter(r"\twice f x -> apply f apply f x");
+    assert_eq!(
+        interpreter.eval("twice 'tail [1 2 3]").unwrap().to_string(),
+        "[3]"
+    );
+    assert_eq!(
+        interpreter.eval(r"twice \n -> * n 3 2").unwrap(),
+        Value::Int(18)
+    );
+}
+
+#[test]
+fn eval_can_start_with_a_lambda() {
+    let mut interpreter = Interpreter::new();
+    let identity = interpreter.eval(r"\x -> x").unwrap();
+    let result = interpreter.apply(&identity, vec![Value::Int(7)]).unwrap();
+    assert_eq!(result, Value::Int(7));
+}
@@ -0,0 +1,70 @@
+use f::{Code, Interpreter, Value};
+
+fn eval(src: &str) -> f::Result<Value> {
+    Interpreter::new().eval(src)
+}
+
+fn shows(src: &str) -> String {
+    eval(src).unwrap().to_string()
+}
+
+#[test]
+fn literals() {
+    assert_eq!(
+        eval("[1 2 3]").unwrap(),
+        Value::List([1, 2, 3].into_iter().map(Value::Int).collect())
+    );
+    assert_eq!(eval("[]").unwrap(), Value::List(Default::default()));
+    assert_eq!(shows("[[1] [] [2 [3]]]"), "[[1], [], [2, [3]]]");
+    assert_eq!(shows("[+ 1 2 \"a\" [true]]"), "[3, a, [true]]");
+}
+
+#[test]
+fn unclosed_brackets() {
+    let mut interpreter = Interpreter::new();
+    let error = interpreter.eval("len [1 2").unwrap_err();
+    assert_eq!(error.code(), Some(Code::UnexpectedEof));
+    let (src, span) = interpreter.sources().find(&error.span().unwrap()).unwrap();
+    assert_eq!(span, 4..5);
+    assert_eq!(&src[span], "[");
+}
+
+#[test]
+fn builtins() {
+    let cases = [
+        ("head [1 2]", "1"),
+        ("tail [1 2 3]", "[2, 3]"),
+        ("push [1 2] 3", "[1, 2, 3]"),
+        ("len [1 2 3]", "3"),
+        ("empty? []", "true"),
+        ("nth [1 2 3] 1", "2"),
+        ("reverse [1 2 3]", "[3, 2, 1]"),
+        ("range 2 5", "[2, 3, 4]"),
+        ("take [1 2 3] 2", "[1, 2]"),
+        ("drop [1 2 3] 5", "[]"),
+        ("concat [1] [2 3]", "[1, 2, 3]"),
+        ("sort [3 1 2]", "[1, 2, 3]"),
+        ("zip [1 2] [3 4 5]", "[[1, 3], [2, 4]]"),
+        ("contains [1 2] 2", "true"),
+        ("flatten [[1] [2 3] []]", "[1, 2, 3]"),
+        ("dedup [1 1 2 1]", "[1, 2]"),
+        ("index-of [1 2 3] 3", "2"),
+        ("index-of [1] 9", "-1"),
+    ];
+    for (src, expected) in cases {
+        assert_eq!(shows(src), expected, "{src}");
+    }
+}
+
+#[test]
+fn builtin_errors() {
+    let cases = [
+        ("head []", Code::EmptyList),
+        ("tail []", Code::EmptyList),
+        ("nth [1] 5", Code::IndexOutOfBounds),
+        ("take [1] -1", Code::IndexOutOfBounds),
+    ];
+    for (src, code) in cases {
+        assert_eq!(eval(src).unwrap_err().code(), Some(code), "{src}");
+    }
+}
@@ -0,0 +1,55 @@
+use f::{Code, Interpreter, Value};
+
+fn eval(src: &str) -> f::Result<Value> {
+    Interpreter::new().eval(src)
+}
+
+#[test]
+fn integers_stay_integers() {
+    assert_eq!(eval("+ 1 2").unwrap(), Value::Int(3));
+    assert_eq!(eval("* -4 5").unwrap(), Value::Int(-20));
+    assert_eq!(eval("// 7 2").unwrap(), Value::Int(3));
+    assert_eq!(eval("% 7 2").unwrap(), Value::Int(1));
+    assert_eq!(eval("% -7 2").unwrap(), Value::Int(1));
+}
+
+#[test]
+fn floats_promote() {
+    assert_eq!(eval("+ 1 2.5").unwrap(), Value::Float(3.5));
+    assert_eq!(eval("/ 7 2").unwrap(), Value::Float(3.5));
+    assert_eq!(eval("/ 6 3").unwrap(), Value::Float(2.0));
+    assert_eq!(eval("% 7.5 2").unwrap(), Value::Float(1.5));
+    assert_eq!(eval("6.02e23").unwrap(), Value::Float(6.02e23));
+}
+
+#[test]
+fn numbers_compare_by_value() {
+    assert_eq!(eval("= 1 1.0").unwrap(), Value::Bool(true));
+    assert_eq!(eval("< 1 1.5").unwrap(), Value::Bool(true));
+}
+
+#[test]
+fn arithmetic_errors() {
+    for src in [
+        "* 9223372036854775807 2",
+        "- -9223372036854775807 2",
+        "/ 1 0",
+        "// 1 0",
+    ] {
+        let error = eval(src).unwrap_err();
+        assert_eq!(error.code(), Some(Code::Arithmetic), "{src}");
+    }
+}
+
+#[test]
+fn out_of_range_literals() {
+    for src in ["9223372036854775808", "1e400", "-1e400"] {
+        let error = eval(src).unwrap_err();
+        assert_eq!(error.code(), Some(Code::InvalidToken), "{src}");
+    }
+    // and the same text doesn't convert at runtime
+    assert_eq!(
+        eval(r#"str->num "1e400""#).unwrap(),
+        Value::List(Default::default())
+    );
+}
@@ -0,0 +1,99 @@
+use f::{
+    error::{Error, TraceFrame},
+    Code, Interpreter, Value,
+};
+
+fn interpreter(src: &str) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    interpreter.load(src).unwrap();
+    interpreter
+}
+
+#[test]
+fn tail_calls_run_in_constant_depth() {
+    let mut interpreter = interpreter(r"\count n acc -> if = n 0 acc count - n 1 + acc 1");
+    interpreter.set_max_depth(100);
+    let result = interpreter.eval("count 100000 0").unwrap();
+    assert_eq!(result, Value::Int(100000));
+}
+
+#[test]
+fn mutual_tail_calls() {
+    let mut interpreter =
+        interpreter("\\even n -> if = n 0 true odd - n 1\n\\odd n -> if = n 0 false even - n 1");
+    interpreter.set_max_depth(100);
+    assert_eq!(interpreter.eval("even 10001").unwrap(), Value::Bool(false));
+}
+
+#[test]
+fn depth_limit_is_an_error() {
+    let mut interpreter = interpreter(r"\sum n -> if = n 0 0 + n sum - n 1");
+    interpreter.set_max_depth(50);
+    assert_eq!(interpreter.eval("sum 40").unwrap(), Value::Int(820));
+
+    let error = interpreter.eval("sum 100").unwrap_err();
+    assert_eq!(error.code(), Some(Code::CallDepth));
+}
+
+#[test]
+fn default_depth_limit_fails_gracefully() {
+    let mut interpreter = interpreter(r"\forever n -> + 1 forever n");
+    let error = interpreter.eval("forever 0").unwrap_err();
+    assert_eq!(error.code(), Some(Code::CallDepth));
+}
+
+#[test]
+fn traces_collapse_repeated_calls() {
+    let interpreter =
+        interpreter("\\down n -> if = n 0 head [] + 1 down - n 1\n\\start -> + 1 down 30");
+    let Error::Traced(error, trace) = interpreter.call("start", vec![]).unwrap_err() else {
+        panic!("runtime errors should have a trace");
+    };
+    assert_eq!(error.code(), Some(Code::EmptyList));
+
+    let calls = trace
+        .iter()
+        .map(|frame| match frame {
+            TraceFrame::Call {
+                function, repeated, ..
+            } => (function.as_str(), *repeated),
+            TraceFrame::Omitted(_) => panic!("the trace is short enough to show whole"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(calls, [("start", 1), ("down", 1), ("down", 30)]);
+}
+
+#[test]
+fn long_traces_leave_out_the_middle() {
+    let interpreter =
+        interpreter("\\ping n -> + 1 pong n\n\\pong n -> if = n 0 head [] + 1 ping - n 1");
+    let Error::Traced(_, trace) = interpreter.call("ping", vec![Value::Int(20)]).unwrap_err()
+    else {
+        panic!("runtime errors should have a trace");
+    };
+    let omitted = trace
+        .iter()
+        .filter_map(|frame| match frame {
+            TraceFrame::Omitted(n) => Some(*n),
+            TraceFrame::Call { .. } => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(trace.len(), 11);
+    assert_eq!(omitted, [42 - 10]);
+}
+
+#[test]
+fn deeply_nested_calls_parse_and_check() {
+    let n = 2000;
+    let programs = [
+        format!("\\main -> {}0", "+ 1 ".repeat(n)),
+        format!("\\main -> {}0{}", "(+ 1 ".repeat(n), ")".repeat(n)),
+        format!("\\main -> {}0{}", "{1 + ".repeat(n), "}".repeat(n)),
+    ];
+    for program in programs {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_type_check(true);
+        interpreter.load(&program).unwrap();
+        assert!(interpreter.call("main", vec![]).is_ok());
+    }
+}
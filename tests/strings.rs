@@ -0,0 +1,54 @@
+use f::{Code, Interpreter, Value};
+
+fn eval(src: &str) -> f::Result<Value> {
+    Interpreter::new().eval(src)
+}
+
+#[test]
+fn builtins() {
+    let cases = [
+        (r#"str-len "héllo""#, "5"),
+        (r#"substring "héllo" 1 3"#, "él"),
+        (r#"char-at "héllo" 1"#, "é"),
+        (r#"chars "ab""#, "[a, b]"),
+        (r#"split "a,b,,c" ",""#, "[a, b, , c]"),
+        (r#"split "ab" """#, "[a, b]"),
+        (r#"join ["a" "b"] ", ""#, "a, b"),
+        (r#"trim "  a b ""#, "a b"),
+        (r#"upper "abc""#, "ABC"),
+        (r#"lower "ABC""#, "abc"),
+        (r#"str-contains "hello" "ell""#, "true"),
+        (r#"starts-with "hello" "he""#, "true"),
+        (r#"ends-with "hello" "he""#, "false"),
+        (r#"replace "a-b-c" "-" "+""#, "a+b+c"),
+        ("num->str 2.5", "2.5"),
+    ];
+    for (src, expected) in cases {
+        assert_eq!(eval(src).unwrap().to_string(), expected, "{src}");
+    }
+}
+
+#[test]
+fn conversions_fail_with_an_empty_list() {
+    assert_eq!(eval(r#"str->num "42""#).unwrap().to_string(), "[42]");
+    assert_eq!(eval(r#"str->num "-1.5""#).unwrap().to_string(), "[-1.5]");
+    assert_eq!(eval(r#"str->num "abc""#).unwrap().to_string(), "[]");
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_type_check(true);
+    interpreter
+        .load("\\parse s -> match str->num s [\n  [n] -> n\n  _ -> 0\n]")
+        .unwrap();
+    assert_eq!(interpreter.eval(r#"parse "x""#).unwrap(), Value::Int(0));
+}
+
+#[test]
+fn indexes_out_of_bounds() {
+    for src in [r#"char-at "ab" 5"#, r#"substring "ab" 1 5"#] {
+        assert_eq!(
+            eval(src).unwrap_err().code(),
+            Some(Code::IndexOutOfBounds),
+            "{src}"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use f::{Code, Interpreter, Value};
+
+fn eval(src: &str) -> f::Result<Value> {
+    Interpreter::new().eval(src)
+}
+
+#[test]
+fn parentheses_group() {
+    assert_eq!(eval("(* 2 (+ 1 2))").unwrap(), eval("* 2 + 1 2").unwrap());
+    assert_eq!(eval("apply (+ 1) 2").unwrap(), Value::Int(3));
+}
+
+#[test]
+fn variadic_calls() {
+    assert_eq!(eval("(+ 1 2 3 4)").unwrap(), Value::Int(10));
+    assert_eq!(eval("(- 10 1 2)").unwrap(), Value::Int(7));
+    assert_eq!(
+        eval("(concat [1] [2] [3])").unwrap().to_string(),
+        "[1, 2, 3]"
+    );
+
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .load("\\add x: Num y: Num -> Num -> + x y\n\\twice f x -> (f (f x))")
+        .unwrap();
+    assert_eq!(interpreter.eval("(add 1 2 3)").unwrap(), Value::Int(6));
+    assert_eq!(interpreter.eval("twice (+ 1) 1").unwrap(), Value::Int(3));
+}
+
+#[test]
+fn parenthesis_errors() {
+    let cases = [
+        ("(head [1] [2])", Code::TooManyArguments),
+        ("()", Code::UnexpectedToken),
+        ("(+ 1 2", Code::UnexpectedEof),
+    ];
+    for (src, code) in cases {
+        assert_eq!(eval(src).unwrap_err().code(), Some(code), "{src}");
+    }
+}
+
+#[test]
+fn infix_precedence() {
+    let cases = [
+        ("{1 + 2 * 3}", 7),
+        ("{(1 + 2) * 3}", 9),
+        ("{10 - 3 - 2}", 5),
+        ("{2 * 3 // 4}", 1),
+    ];
+    for (src, expected) in cases {
+        assert_eq!(eval(src).unwrap(), Value::Int(expected), "{src}");
+    }
+    assert_eq!(
+        eval("{true or false and false}").unwrap(),
+        Value::Bool(true)
+    );
+}
+
+#[test]
+fn declared_operators() {
+    let mut interpreter = Interpreter::new();
+    interpreter
+        .load(
+            r"
+\max a b -> if {a > b} a b
+\infixl 5 max
+\pow a b -> if {b = 0} 1 {a * pow a {b - 1}}
+\infixr 8 pow
+\fac n -> if {n = 0} 1 {n * fac {n - 1}}
+",
+        )
+        .unwrap();
+    assert_eq!(interpreter.eval("fac 5").unwrap(), Value::Int(120));
+    assert_eq!(
+        interpreter.eval("{1 + 2 max 3 + 4}").unwrap(),
+        Value::Int(7)
+    );
+    assert_eq!(
+        interpreter.eval("{2 pow 3 pow 2}").unwrap(),
+        Value::Int(512)
+    );
+}
+
+#[test]
+fn infix_errors() {
+    let cases = [
+        ("{1 < 2 < 3}", Code::InvalidOperator),
+        ("{1 head 2}", Code::InvalidOperator),
+        ("{1 +}", Code::UnexpectedToken),
+        ("{1 + 2", Code::UnexpectedEof),
+    ];
+    for (src, code) in cases {
+        assert_eq!(eval(src).unwrap_err().code(), Some(code), "{src}");
+    }
+}